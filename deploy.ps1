@@ -16,19 +16,25 @@ if ($LASTEXITCODE -ne 0) {
 Pop-Location
 
 Write-Host "Deploying to $RemoteHost..."
-# Kill existing instances to release file lock
-ssh $RemoteHost "pkill tcp-bridge || true; pkill api-server || true"
+# Kill api-server to release file lock; a running bridge keeps going and
+# is replaced with run-proxy.sh --handover
+ssh $RemoteHost "pkill api-server || true"
 
 # Deploy
+scp -O ./tcp-bridge/target/$Target/release/tcp-bridge "$($RemoteHost):/tmp/tcp-bridge.new"
+ssh $RemoteHost "mv -f /tmp/tcp-bridge.new /tmp/tcp-bridge"
 scp -O `
-    ./tcp-bridge/target/$Target/release/tcp-bridge `
     ./tcp-bridge/target/$Target/release/bridge-ctl `
     ./tcp-bridge/target/$Target/release/api-server `
     ./scripts/run-sniffer.sh `
     ./scripts/run-proxy.sh `
     ./scripts/test_mappings.sh `
     ./scripts/run-api.sh `
+    ./scripts/commands.json `
+    ./scripts/bridge.toml `
     "$($RemoteHost):/tmp/"
 ssh $RemoteHost "chmod +x  /tmp/bridge-ctl /tmp/api-server /tmp/run-sniffer.sh /tmp/tcp-bridge /tmp/run-proxy.sh /tmp/test_mappings.sh /tmp/run-api.sh"
+# The config lives outside /tmp; keep one that is already installed
+ssh $RemoteHost "[ -e /etc/bridge.toml ] || install -m 644 /tmp/bridge.toml /etc/bridge.toml"
 
 Write-Host "Done."
@@ -45,18 +45,23 @@ fi
 
 echo " -> Deploying to $REMOTE_HOST..."
 
-# Kill existing processes
-ssh "$REMOTE_HOST" "pkill tcp-bridge || true"
+# A running bridge keeps going: upload next to it and rename over it
+scp "$BIN_TCP_BRIDGE" "$REMOTE_HOST:$REMOTE_TMP/tcp-bridge.new"
+ssh "$REMOTE_HOST" "mv -f $REMOTE_TMP/tcp-bridge.new $REMOTE_TMP/tcp-bridge"
 
 # SCP Files
-scp "$BIN_TCP_BRIDGE" \
-    "$BIN_BRIDGE_CTL" \
+scp "$BIN_BRIDGE_CTL" \
     "$PROJECT_ROOT/scripts/run-proxy.sh" \
     "$PROJECT_ROOT/scripts/run-sniffer.sh" \
     "$PROJECT_ROOT/scripts/test_mappings.sh" \
+    "$PROJECT_ROOT/scripts/commands.json" \
+    "$PROJECT_ROOT/scripts/bridge.toml" \
     "$REMOTE_HOST:$REMOTE_TMP/"
 
 # Make executable
 ssh "$REMOTE_HOST" "chmod +x $REMOTE_TMP/tcp-bridge $REMOTE_TMP/bridge-ctl $REMOTE_TMP/*.sh"
 
-echo " -> Done. Run '/tmp/run-proxy.sh' on device to start."
+# The config lives outside /tmp; keep one that is already installed
+ssh "$REMOTE_HOST" "[ -e /etc/bridge.toml ] || install -m 644 $REMOTE_TMP/bridge.toml /etc/bridge.toml"
+
+echo " -> Done. Run '/tmp/run-proxy.sh' on device to start, or '/tmp/run-proxy.sh --handover' to replace a running bridge without disconnecting the GUI."
@@ -5,5 +5,5 @@
 PORT=${1:-8080}
 
 echo "Starting API server on port $PORT..."
-/tmp/api-server &
+/tmp/api-server --bind "0.0.0.0:$PORT" &
 echo "API server started with PID $!"
@@ -0,0 +1,250 @@
+//! `#[derive(RodeCommand)]` for single-property Rodecaster commands.
+//!
+//! One declaration generates both `RodeCommand::build_payload` and
+//! `RodeDecode::decode_payload`, so encoding and decoding cannot drift apart.
+//!
+//! ```ignore
+//! #[derive(RodeCommand)]
+//! #[rode(property = "channelOutputMute", base = 0x1C, ty = 0x01)]
+//! pub struct ChannelOutputMute {
+//!     #[rode(index)]
+//!     pub fader_index: u8,
+//!     pub mute: bool,
+//! }
+//! ```
+//!
+//! ## Struct attributes
+//! - `property` (required): property string, NUL is appended
+//! - `ty` (required): type byte written after the `0x01` marker
+//! - `base`: added to the index field (or used as-is for structs without one)
+//! - `preamble = [..]`: fixed bytes sent instead of the session ID
+//! - `value = <expr>`: constant value for structs without a value field
+//!
+//! ## Field attributes
+//! - `#[rode(index)]`: the `u8` index field, or a `[u8; 2]` two-byte prefix
+//!   (base added to the first byte, e.g. CallMe mix routing); the remaining
+//!   field is the value
+//!
+//! ## Decoding
+//! The payload must be exactly what `build_payload` would produce for the
+//! decoded fields: the value is encoded again and compared with the rest, so
+//! trailing bytes and non-canonical values are rejected.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Expr, ExprArray, Fields, Ident, LitByteStr, LitInt, LitStr};
+
+#[proc_macro_derive(RodeCommand, attributes(rode))]
+pub fn derive_rode_command(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match expand(input) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+#[derive(Default)]
+struct StructAttrs {
+    property: Option<LitStr>,
+    base: Option<LitInt>,
+    ty: Option<LitInt>,
+    preamble: Option<Vec<Expr>>,
+    value: Option<Expr>,
+}
+
+fn parse_struct_attrs(input: &DeriveInput) -> syn::Result<StructAttrs> {
+    let mut attrs = StructAttrs::default();
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("rode")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("property") {
+                attrs.property = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("base") {
+                attrs.base = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("ty") {
+                attrs.ty = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("value") {
+                attrs.value = Some(meta.value()?.parse()?);
+            } else if meta.path.is_ident("preamble") {
+                let bytes: ExprArray = meta.value()?.parse()?;
+                attrs.preamble = Some(bytes.elems.into_iter().collect());
+            } else {
+                return Err(meta.error("unknown rode attribute"));
+            }
+            Ok(())
+        })?;
+    }
+    Ok(attrs)
+}
+
+fn is_index_field(field: &syn::Field) -> syn::Result<bool> {
+    let mut index = false;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("rode")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("index") {
+                index = true;
+                Ok(())
+            } else {
+                Err(meta.error("unknown rode field attribute"))
+            }
+        })?;
+    }
+    Ok(index)
+}
+
+/// Whether an index field is `u8` (false) or `[u8; 2]` (true)
+fn is_two_byte_index(field: &syn::Field) -> syn::Result<bool> {
+    let is_u8 = |ty: &syn::Type| matches!(ty, syn::Type::Path(p) if p.qself.is_none() && p.path.is_ident("u8"));
+    match &field.ty {
+        ty if is_u8(ty) => Ok(false),
+        syn::Type::Array(array) if is_u8(&array.elem) && matches!(
+            &array.len,
+            Expr::Lit(syn::ExprLit { lit: syn::Lit::Int(len), .. }) if len.base10_digits() == "2"
+        ) => Ok(true),
+        ty => Err(syn::Error::new_spanned(ty, "index field must be `u8` or `[u8; 2]`")),
+    }
+}
+
+fn expand(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let attrs = parse_struct_attrs(&input)?;
+
+    let property = attrs.property.as_ref()
+        .ok_or_else(|| syn::Error::new_spanned(name, "missing #[rode(property = \"...\")]"))?;
+    let ty = attrs.ty.as_ref()
+        .ok_or_else(|| syn::Error::new_spanned(name, "missing #[rode(ty = ...)]"))?;
+    let base = match &attrs.base {
+        Some(base) => quote!(#base),
+        None => quote!(0x00),
+    };
+    let property_bytes = LitByteStr::new(
+        format!("{}\0", property.value()).as_bytes(),
+        property.span(),
+    );
+
+    let Data::Struct(data) = &input.data else {
+        return Err(syn::Error::new_spanned(name, "RodeCommand can only be derived for structs"));
+    };
+
+    // Split fields into the index field and the value field
+    let mut index_field: Option<Ident> = None;
+    let mut two_byte_index = false;
+    let mut value_field: Option<Ident> = None;
+    match &data.fields {
+        Fields::Named(fields) => {
+            for field in &fields.named {
+                let ident = field.ident.clone().unwrap();
+                if is_index_field(field)? {
+                    two_byte_index = is_two_byte_index(field)?;
+                    index_field = Some(ident);
+                } else if value_field.replace(ident).is_some() {
+                    return Err(syn::Error::new_spanned(field, "only one value field is supported"));
+                }
+            }
+        }
+        Fields::Unit => {}
+        Fields::Unnamed(_) => {
+            return Err(syn::Error::new_spanned(name, "tuple structs are not supported"));
+        }
+    }
+
+    let protocol = quote!(::tcp_bridge::protocol);
+
+    // Preamble: session ID or fixed bytes
+    let (write_preamble, read_preamble) = match &attrs.preamble {
+        Some(bytes) => (
+            quote!(payload.extend_from_slice(&[#(#bytes),*]);),
+            quote!(let rest = payload.strip_prefix(&[#(#bytes),*][..])?;),
+        ),
+        None => (
+            quote!(payload.extend_from_slice(session_id);),
+            quote!(let rest = payload.get(#protocol::SESSION_ID_LEN..)?;),
+        ),
+    };
+
+    let (write_index, read_index) = match &index_field {
+        Some(field) if two_byte_index => (
+            quote! {
+                payload.push((#base as u8).wrapping_add(self.#field[0]));
+                payload.push(self.#field[1]);
+            },
+            quote! {
+                let (&second, rest) = rest.split_first()?;
+                let #field = [index.checked_sub(#base as u8)?, second];
+            },
+        ),
+        Some(field) => (
+            quote!(payload.push((#base as u8).wrapping_add(self.#field));),
+            quote!(let #field = index.checked_sub(#base as u8)?;),
+        ),
+        None => (
+            quote!(payload.push(#base as u8);),
+            quote!(if index != #base as u8 { return None; }),
+        ),
+    };
+
+    let (write_value, read_value) = match (&value_field, &attrs.value) {
+        (Some(field), None) => (
+            quote!(#protocol::WireValue::encode(&self.#field, &mut payload);),
+            quote! {
+                let #field = #protocol::WireValue::decode(rest)?;
+                let mut expected = Vec::new();
+                #protocol::WireValue::encode(&#field, &mut expected);
+            },
+        ),
+        (None, Some(value)) => (
+            quote!(#protocol::WireValue::encode(&#value, &mut payload);),
+            quote! {
+                let mut expected = Vec::new();
+                #protocol::WireValue::encode(&#value, &mut expected);
+            },
+        ),
+        (Some(_), Some(_)) => {
+            return Err(syn::Error::new_spanned(name, "value attribute conflicts with a value field"));
+        }
+        (None, None) => {
+            return Err(syn::Error::new_spanned(name, "needs a value field or #[rode(value = ...)]"));
+        }
+    };
+
+    let construct = match &data.fields {
+        Fields::Named(_) => {
+            let fields = index_field.iter().chain(value_field.iter());
+            quote!(Self { #(#fields),* })
+        }
+        _ => quote!(Self),
+    };
+
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        impl #impl_generics #protocol::RodeCommand for #name #ty_generics #where_clause {
+            fn build_payload(&self, session_id: &[u8]) -> Vec<u8> {
+                let _ = session_id;
+                let mut payload = Vec::new();
+                #write_preamble
+                #write_index
+                payload.extend_from_slice(#property_bytes);
+                payload.push(0x01);
+                payload.push(#ty);
+                #write_value
+                payload
+            }
+        }
+
+        impl #impl_generics #protocol::RodeDecode for #name #ty_generics #where_clause {
+            fn decode_payload(payload: &[u8]) -> Option<Self> {
+                #read_preamble
+                let (&index, rest) = rest.split_first()?;
+                #read_index
+                let rest = rest.strip_prefix(&#property_bytes[..])?;
+                let rest = rest.strip_prefix(&[0x01, #ty][..])?;
+                #read_value
+                if rest != expected.as_slice() {
+                    return None;
+                }
+                Some(#construct)
+            }
+        }
+    })
+}
@@ -3,24 +3,48 @@
 
 use axum::{
     extract::State,
-    http::StatusCode,
+    http::{header, StatusCode},
     response::Json,
     routing::{get, post},
     Router,
 };
+use clap::Parser;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
 use std::sync::Arc;
-use tokio::io::AsyncWriteExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::UnixStream;
 
-use tcp_bridge::commands::{Command, MixAction};
+use tcp_bridge::commands::{Command, CommandError, CommandReply, CommandWarning, MixAction};
+use tcp_bridge::config::Config;
+use tcp_bridge::faders::FaderStatus;
 use tcp_bridge::names::{MixOutput, Source, Fader};
+use tcp_bridge::routing::{MatrixSnapshot, MixState, RoutingReply};
+use tcp_bridge::status::BridgeStatus;
 
-const SOCKET_PATH: &str = "/tmp/socket_bridge_control";
+/// Settings default to the config file (see `tcp_bridge::config`); flags override it
+#[derive(Parser, Debug)]
+#[command(author, version, about = "HTTP API for the Rodecaster bridge")]
+struct Args {
+    /// Config file (default /etc/bridge.toml if it exists, or BRIDGE_CONFIG)
+    #[arg(long)]
+    config: Option<PathBuf>,
+    /// Override a config setting, e.g. --setting api.bind=127.0.0.1:8080
+    #[arg(long = "setting", value_name = "SECTION.KEY=VALUE")]
+    overrides: Vec<String>,
+    /// Address to listen on, e.g. 0.0.0.0:8080
+    #[arg(long)]
+    bind: Option<String>,
+    /// The bridge's control socket
+    #[arg(long)]
+    socket: Option<PathBuf>,
+}
 
 #[derive(Clone)]
 struct AppState {
     socket_path: String,
+    allow_reload: bool,
 }
 
 #[derive(Serialize)]
@@ -30,14 +54,22 @@ struct ApiResponse {
     message: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     error: Option<String>,
+    /// Machine-readable error code (see `CommandError`)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    code: Option<&'static str>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    warnings: Vec<CommandWarning>,
 }
 
 impl ApiResponse {
     fn ok(msg: &str) -> Self {
-        Self { success: true, message: Some(msg.to_string()), error: None }
+        Self { success: true, message: Some(msg.to_string()), error: None, code: None, warnings: Vec::new() }
     }
     fn err(msg: &str) -> Self {
-        Self { success: false, message: None, error: Some(msg.to_string()) }
+        Self { success: false, message: None, error: Some(msg.to_string()), code: None, warnings: Vec::new() }
+    }
+    fn rejected(e: &CommandError) -> Self {
+        Self { code: Some(e.code()), ..Self::err(&e.to_string()) }
     }
 }
 
@@ -48,6 +80,29 @@ struct MixRequest {
     source: Source,
 }
 
+#[derive(Deserialize)]
+struct BusRequest {
+    mix: MixOutput,
+    routes: BTreeMap<String, MixState>,
+}
+
+#[derive(Deserialize)]
+struct MixCopyRequest {
+    from: MixOutput,
+    to: MixOutput,
+}
+
+#[derive(Deserialize)]
+struct MixSwapRequest {
+    first: MixOutput,
+    second: MixOutput,
+}
+
+#[derive(Deserialize)]
+struct MixMinusRequest {
+    remotes: Vec<Source>,
+}
+
 #[derive(Deserialize)]
 struct FaderRequest {
     fader: Fader,
@@ -59,7 +114,39 @@ struct FaderRequest {
     level: Option<f32>,
 }
 
-async fn send_command(socket_path: &str, cmd: &Command) -> Result<(), String> {
+#[derive(Deserialize)]
+struct SpecRequest {
+    name: String,
+    index: u8,
+    #[serde(default)]
+    value: Option<i64>,
+}
+
+async fn send_command(socket_path: &str, cmd: &Command) -> Result<CommandReply, String> {
+    let reply = query(socket_path, cmd).await?;
+    serde_json::from_str(&reply).map_err(|e| format!("Invalid command reply: {}", e))
+}
+
+/// Validate, send and turn the bridge's reply into a response
+async fn dispatch(socket_path: &str, cmd: &Command, msg: &str) -> (StatusCode, Json<ApiResponse>) {
+    if let Err(e) = cmd.validate() {
+        return (StatusCode::BAD_REQUEST, Json(ApiResponse::rejected(&e)));
+    }
+    
+    match send_command(socket_path, cmd).await {
+        Ok(CommandReply { error: Some(e), .. }) => (StatusCode::BAD_REQUEST, Json(ApiResponse::rejected(&e))),
+        Ok(reply) => (StatusCode::OK, Json(ApiResponse { warnings: reply.warnings, ..ApiResponse::ok(msg) })),
+        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::err(&e))),
+    }
+}
+
+async fn query_status(socket_path: &str) -> Result<BridgeStatus, String> {
+    let reply = query(socket_path, &Command::Status).await?;
+    serde_json::from_str(&reply).map_err(|e| format!("Invalid status reply: {}", e))
+}
+
+/// Send a command that the bridge answers on the same connection
+async fn query(socket_path: &str, cmd: &Command) -> Result<String, String> {
     let json = serde_json::to_string(cmd)
         .map_err(|e| format!("Serialization error: {}", e))?;
     
@@ -68,8 +155,13 @@ async fn send_command(socket_path: &str, cmd: &Command) -> Result<(), String> {
     
     stream.write_all(json.as_bytes()).await
         .map_err(|e| format!("Write error: {}", e))?;
+    stream.shutdown().await
+        .map_err(|e| format!("Write error: {}", e))?;
     
-    Ok(())
+    let mut reply = String::new();
+    stream.read_to_string(&mut reply).await
+        .map_err(|e| format!("Read error: {}", e))?;
+    Ok(reply)
 }
 
 async fn health() -> Json<ApiResponse> {
@@ -83,12 +175,56 @@ async fn mix_handler(
     let cmd = Command::Mix { action: req.action, mix: req.mix, source: req.source };
     let msg = format!("{:?} {} in {}", req.action, req.source, req.mix);
     
-    match send_command(&state.socket_path, &cmd).await {
-        Ok(_) => (StatusCode::OK, Json(ApiResponse::ok(&msg))),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::err(&e))),
+    dispatch(&state.socket_path, &cmd, &msg).await
+}
+
+/// Send a bus/copy/swap request and return the bridge's list of changed cells
+async fn routing_request(
+    socket_path: &str,
+    cmd: Command,
+) -> Result<Json<RoutingReply>, (StatusCode, Json<ApiResponse>)> {
+    if let Err(e) = cmd.validate() {
+        return Err((StatusCode::BAD_REQUEST, Json(ApiResponse::rejected(&e))));
+    }
+    
+    let reply = query(socket_path, &cmd).await
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::err(&e))))?;
+    let reply: RoutingReply = serde_json::from_str(&reply)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::err(&format!("Invalid routing reply: {}", e)))))?;
+    match &reply.error {
+        Some(e) => Err((StatusCode::BAD_REQUEST, Json(ApiResponse::rejected(e)))),
+        None => Ok(Json(reply)),
     }
 }
 
+async fn bus_handler(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<BusRequest>,
+) -> Result<Json<RoutingReply>, (StatusCode, Json<ApiResponse>)> {
+    routing_request(&state.socket_path, Command::Bus { mix: req.mix, routes: req.routes }).await
+}
+
+async fn mix_copy_handler(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<MixCopyRequest>,
+) -> Result<Json<RoutingReply>, (StatusCode, Json<ApiResponse>)> {
+    routing_request(&state.socket_path, Command::MixCopy { from: req.from, to: req.to }).await
+}
+
+async fn mix_swap_handler(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<MixSwapRequest>,
+) -> Result<Json<RoutingReply>, (StatusCode, Json<ApiResponse>)> {
+    routing_request(&state.socket_path, Command::MixSwap { first: req.first, second: req.second }).await
+}
+
+async fn mix_minus_handler(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<MixMinusRequest>,
+) -> Result<Json<RoutingReply>, (StatusCode, Json<ApiResponse>)> {
+    routing_request(&state.socket_path, Command::MixMinus { remotes: req.remotes }).await
+}
+
 async fn fader_handler(
     State(state): State<Arc<AppState>>,
     Json(req): Json<FaderRequest>,
@@ -102,25 +238,155 @@ async fn fader_handler(
     
     let msg = format!("Updated {}", req.fader);
     
-    match send_command(&state.socket_path, &cmd).await {
-        Ok(_) => (StatusCode::OK, Json(ApiResponse::ok(&msg))),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::err(&e))),
+    dispatch(&state.socket_path, &cmd, &msg).await
+}
+
+async fn spec_handler(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<SpecRequest>,
+) -> (StatusCode, Json<ApiResponse>) {
+    let msg = format!("Sent {} {}", req.name, req.index);
+    let cmd = Command::Spec { name: req.name, index: req.index, value: req.value };
+    
+    dispatch(&state.socket_path, &cmd, &msg).await
+}
+
+async fn status_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<BridgeStatus>, (StatusCode, Json<ApiResponse>)> {
+    query_status(&state.socket_path).await
+        .map(Json)
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, Json(ApiResponse::err(&e))))
+}
+
+async fn matrix_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<MatrixSnapshot>, (StatusCode, Json<ApiResponse>)> {
+    let reply = query(&state.socket_path, &Command::Matrix).await
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, Json(ApiResponse::err(&e))))?;
+    serde_json::from_str(&reply)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::err(&format!("Invalid matrix reply: {}", e)))))
+}
+
+async fn faders_handler(
+    State(state): State<Arc<AppState>>,
+) -> Result<Json<Vec<FaderStatus>>, (StatusCode, Json<ApiResponse>)> {
+    let reply = query(&state.socket_path, &Command::Faders).await
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, Json(ApiResponse::err(&e))))?;
+    serde_json::from_str(&reply)
+        .map(Json)
+        .map_err(|e| (StatusCode::INTERNAL_SERVER_ERROR, Json(ApiResponse::err(&format!("Invalid faders reply: {}", e)))))
+}
+
+/// Whether `POST /command` may forward `cmd`. Every variant is listed, so a
+/// new one has to be allowed here explicitly.
+fn forwardable(cmd: &Command, state: &AppState) -> Result<(), String> {
+    match cmd {
+        Command::Mix { .. }
+        | Command::Bus { .. }
+        | Command::MixCopy { .. }
+        | Command::MixSwap { .. }
+        | Command::MixMinus { .. }
+        | Command::Fader { .. }
+        | Command::RawSource { .. }
+        | Command::Touch
+        | Command::Spec { .. }
+        | Command::Status
+        | Command::Matrix
+        | Command::Faders
+        | Command::Traffic { .. } => Ok(()),
+        Command::Render { command } => forwardable(command, state),
+        Command::Reload if state.allow_reload => Ok(()),
+        Command::Reload => Err("Reload through the API is disabled (set api.allow_reload = true)".to_string()),
+    }
+}
+
+/// Forward a `Command` and return the bridge's reply unchanged (`bridge-ctl --remote`)
+async fn command_handler(
+    State(state): State<Arc<AppState>>,
+    Json(cmd): Json<Command>,
+) -> Result<([(header::HeaderName, &'static str); 1], String), (StatusCode, Json<ApiResponse>)> {
+    forwardable(&cmd, &state)
+        .map_err(|e| (StatusCode::FORBIDDEN, Json(ApiResponse::err(&e))))?;
+    query(&state.socket_path, &cmd).await
+        .map(|reply| ([(header::CONTENT_TYPE, "application/json")], reply))
+        .map_err(|e| (StatusCode::SERVICE_UNAVAILABLE, Json(ApiResponse::err(&e))))
+}
+
+/// Prometheus text format
+async fn metrics_handler(State(state): State<Arc<AppState>>) -> (StatusCode, String) {
+    let status = match query_status(&state.socket_path).await {
+        Ok(s) => s,
+        Err(_) => return (StatusCode::OK, "rodecaster_bridge_up 0\n".to_string()),
+    };
+    let up = &status.upstream;
+    let mut out = String::new();
+    out.push_str("rodecaster_bridge_up 1\n");
+    out.push_str(&format!("rodecaster_bridge_connections {}\n", status.connections));
+    out.push_str(&format!("rodecaster_bridge_session_known {}\n", status.session_id.is_some() as u8));
+    out.push_str(&format!("rodecaster_mixer_healthy {}\n", up.healthy as u8));
+    out.push_str(&format!("rodecaster_mixer_pings_sent_total {}\n", up.pings_sent));
+    out.push_str(&format!("rodecaster_mixer_pings_answered_total {}\n", up.pings_answered));
+    if let Some(latency) = up.latency_ms {
+        out.push_str(&format!("rodecaster_mixer_ping_latency_ms {}\n", latency));
+    }
+    if let Some(gap) = up.ping_gap_ms {
+        out.push_str(&format!("rodecaster_mixer_ping_gap_ms {}\n", gap));
     }
+    (StatusCode::OK, out)
 }
 
 #[tokio::main]
 async fn main() {
-    let state = Arc::new(AppState { socket_path: SOCKET_PATH.to_string() });
+    let args = Args::parse();
+    let mut config = match Config::load(args.config.as_deref(), &args.overrides) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("[API] {}", e);
+            std::process::exit(2);
+        }
+    };
+    if let Some(bind) = args.bind {
+        config.api.bind = bind;
+    }
+    if let Some(socket) = args.socket {
+        config.control.socket = socket;
+    }
+    let state = Arc::new(AppState {
+        socket_path: config.control.socket.to_string_lossy().into_owned(),
+        allow_reload: config.api.allow_reload,
+    });
 
     let app = Router::new()
         .route("/health", get(health))
+        .route("/status", get(status_handler))
+        .route("/matrix", get(matrix_handler))
+        .route("/faders", get(faders_handler))
+        .route("/metrics", get(metrics_handler))
         .route("/mix", post(mix_handler))
+        .route("/mix/copy", post(mix_copy_handler))
+        .route("/mix/swap", post(mix_swap_handler))
+        .route("/mix/minus", post(mix_minus_handler))
+        .route("/bus", post(bus_handler))
         .route("/fader", post(fader_handler))
+        .route("/spec", post(spec_handler))
+        .route("/command", post(command_handler))
         .with_state(state);
 
-    let addr = "0.0.0.0:8080";
+    let addr = &config.api.bind;
     println!("[API] Starting on {}", addr);
-    
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+
+    // Same exit code as an invalid config: api.bind is where the address comes from
+    let listener = match tokio::net::TcpListener::bind(addr).await {
+        Ok(listener) => listener,
+        Err(e) => {
+            eprintln!("[Config] Cannot listen on api.bind {}: {}", addr, e);
+            std::process::exit(2);
+        }
+    };
+    if let Err(e) = axum::serve(listener, app).await {
+        eprintln!("[API] Server failed: {}", e);
+        std::process::exit(2);
+    }
 }
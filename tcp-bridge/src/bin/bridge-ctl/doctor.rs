@@ -0,0 +1,234 @@
+//! `bridge-ctl doctor`: checks the deployment on the device.
+//!
+//! Covers what `tcp-bridge --setup-network` sets up (IP alias, DNAT
+//! rule, proxy process) and what the bridge reports once the GUI is proxied
+//! (connection, session ID, traffic in both directions). Each failure
+//! comes with a hint on how to fix it.
+
+use serde::Serialize;
+use std::os::unix::fs::FileTypeExt;
+
+use tcp_bridge::commands::Command;
+use tcp_bridge::network::{self, GUI_PROCESS, NetworkSetup};
+use tcp_bridge::status::BridgeStatus;
+
+use crate::error::CtlError;
+use crate::{Target, query};
+
+/// What the proxy is expected to look like
+pub struct Setup {
+    pub network: NetworkSetup,
+    /// Longest gap without a frame in either direction
+    pub max_idle_ms: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Outcome {
+    Pass,
+    Fail,
+    /// Could not be checked (missing tool, no permission, bridge down)
+    Skip,
+}
+
+#[derive(Debug, Serialize)]
+struct Check {
+    name: &'static str,
+    outcome: Outcome,
+    detail: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    fix: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Report<'a> {
+    ok: bool,
+    checks: &'a [Check],
+}
+
+impl Check {
+    fn pass(name: &'static str, detail: impl Into<String>) -> Self {
+        Check { name, outcome: Outcome::Pass, detail: detail.into(), fix: None }
+    }
+
+    fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Check { name, outcome: Outcome::Fail, detail: detail.into(), fix: Some(fix.into()) }
+    }
+
+    fn skip(name: &'static str, detail: impl Into<String>) -> Self {
+        Check { name, outcome: Outcome::Skip, detail: detail.into(), fix: None }
+    }
+}
+
+pub async fn run(target: &Target, setup: &Setup, json: bool) -> Result<(), CtlError> {
+    let Target::Socket(socket) = target else {
+        return Err(CtlError::Invalid("doctor checks the device itself; run it there without --remote".to_string()));
+    };
+
+    let mut checks = vec![
+        setup.check_alias(),
+        setup.check_dnat(),
+        check_process(),
+        setup.check_listening(),
+    ];
+    let (socket_check, status) = check_socket(target, socket).await;
+    checks.push(socket_check);
+    match status {
+        Some(status) => {
+            checks.push(check_gui(&status));
+            checks.push(check_session(&status));
+            checks.extend(setup.check_traffic(&status));
+        }
+        None => {
+            for name in ["GUI connection", "Session ID", "Traffic to mixer", "Traffic from mixer"] {
+                checks.push(Check::skip(name, "needs a reachable bridge"));
+            }
+        }
+    }
+
+    let failed = checks.iter().filter(|c| c.outcome == Outcome::Fail).count();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&Report { ok: failed == 0, checks: &checks })?);
+    } else {
+        print_report(&checks);
+    }
+    match failed {
+        0 => Ok(()),
+        n => Err(CtlError::ChecksFailed(format!("{} of {} checks failed", n, checks.len()))),
+    }
+}
+
+impl Setup {
+    fn check_alias(&self) -> Check {
+        const NAME: &str = "IP alias";
+        let mut missing = Vec::new();
+        for ip in self.network.aliases() {
+            match network::has_alias(ip) {
+                Ok(true) => {}
+                Ok(false) => missing.push(ip),
+                Err(e) => return Check::skip(NAME, e),
+            }
+        }
+        match missing.as_slice() {
+            [] => Check::pass(NAME, format!("{:?} on lo", self.network.aliases())),
+            [ip, ..] => Check::fail(NAME, format!("{} is not configured on lo", ip),
+                network::shell(&network::alias_command("add", *ip))),
+        }
+    }
+
+    fn check_dnat(&self) -> Check {
+        const NAME: &str = "iptables DNAT rule";
+        let net = &self.network;
+        match net.count_rules() {
+            Ok(1) => Check::pass(NAME, format!("{}:{} -> {}:{}", net.target_ip, net.target_port, net.bind_ip, net.bind_port)),
+            Ok(0) => Check::fail(NAME, "no rule redirects the GUI to the proxy",
+                network::shell(&net.iptables("-A"))),
+            Ok(n) => Check::fail(NAME, format!("rule present {} times", n),
+                format!("{} (run {} times)", network::shell(&net.iptables("-D")), n - 1)),
+            Err(e) => Check::skip(NAME, e),
+        }
+    }
+
+    fn check_listening(&self) -> Check {
+        const NAME: &str = "Proxy port";
+        let (ip, port) = (self.network.bind_ip, self.network.bind_port);
+        match network::is_listening(ip, port) {
+            Ok(true) => Check::pass(NAME, format!("listening on {}:{}", ip, port)),
+            Ok(false) => Check::fail(NAME, format!("nothing listens on {}:{}", ip, port),
+                "restart the bridge with scripts/run-proxy.sh"),
+            Err(e) => Check::skip(NAME, e),
+        }
+    }
+
+    fn check_traffic(&self, status: &BridgeStatus) -> [Check; 2] {
+        let check = |name, age: Option<u64>, fix: &str| match age {
+            Some(ms) if ms <= self.max_idle_ms => Check::pass(name, format!("last frame {} ms ago", ms)),
+            Some(ms) => Check::fail(name, format!("last frame {} s ago", ms / 1000), fix),
+            None => Check::fail(name, "no frame seen yet", fix),
+        };
+        [
+            check("Traffic to mixer", status.last_to_mixer_ms,
+                "the GUI is not talking through the proxy: pkill rc_gui_juce so it reconnects"),
+            check("Traffic from mixer", status.last_from_mixer_ms,
+                "the mixer is not answering: check `bridge-ctl status` and restart rc_audio_mixer"),
+        ]
+    }
+}
+
+fn check_process() -> Check {
+    const NAME: &str = "Proxy process";
+    let pids = match network::pids_of("tcp-bridge") {
+        Ok(pids) => pids,
+        Err(e) => return Check::skip(NAME, e),
+    };
+    // With --setup-network the proxy is the child of a supervising tcp-bridge
+    let pids: Vec<u32> = pids.iter().copied()
+        .filter(|pid| !network::parent_pid(*pid).is_some_and(|ppid| pids.contains(&ppid)))
+        .collect();
+    match pids.as_slice() {
+        [] => Check::fail(NAME, "tcp-bridge is not running", "start it with scripts/run-proxy.sh"),
+        [pid] => Check::pass(NAME, format!("tcp-bridge running (pid {})", pid)),
+        _ => Check::fail(NAME, format!("{} instances running (pids {:?})", pids.len(), pids),
+            "pkill tcp-bridge, then start one with scripts/run-proxy.sh"),
+    }
+}
+
+/// Control socket state, plus the bridge status if it answers
+async fn check_socket(target: &Target, path: &str) -> (Check, Option<BridgeStatus>) {
+    const NAME: &str = "Control socket";
+    match std::fs::symlink_metadata(path) {
+        Err(_) => return (Check::fail(NAME, format!("{} does not exist", path), "start the bridge with scripts/run-proxy.sh"), None),
+        Ok(meta) if !meta.file_type().is_socket() => {
+            return (Check::fail(NAME, format!("{} is not a socket", path), format!("rm {} and restart the bridge", path)), None);
+        }
+        Ok(_) => {}
+    }
+    let status = match query(target, &Command::Status).await {
+        Ok(reply) => serde_json::from_str::<BridgeStatus>(&reply),
+        Err(_) => {
+            let check = Check::fail(NAME, format!("{} exists but no bridge answers (stale)", path),
+                format!("rm {} and restart the bridge with scripts/run-proxy.sh", path));
+            return (check, None);
+        }
+    };
+    match status {
+        Ok(status) => (Check::pass(NAME, format!("bridge answers on {}", path)), Some(status)),
+        Err(e) => (Check::fail(NAME, format!("unreadable status reply: {}", e), "bridge and bridge-ctl versions differ; redeploy both"), None),
+    }
+}
+
+fn check_gui(status: &BridgeStatus) -> Check {
+    const NAME: &str = "GUI connection";
+    if status.connections > 0 {
+        return Check::pass(NAME, format!("{} connection(s) through the proxy", status.connections));
+    }
+    let gui = match network::pids_of(GUI_PROCESS) {
+        Ok(pids) if pids.is_empty() => format!("{} is not running", GUI_PROCESS),
+        Ok(_) => format!("{} is running but connected around the proxy", GUI_PROCESS),
+        Err(_) => "no GUI connected".to_string(),
+    };
+    Check::fail(NAME, gui, format!("check the DNAT rule, then pkill {} so it reconnects through the proxy", GUI_PROCESS))
+}
+
+fn check_session(status: &BridgeStatus) -> Check {
+    const NAME: &str = "Session ID";
+    match &status.session_id {
+        Some(session) => Check::pass(NAME, format!("sniffed {}", session)),
+        None => Check::fail(NAME, "not sniffed yet",
+            "touch the screen or change a control on the device so the GUI sends a command"),
+    }
+}
+
+fn print_report(checks: &[Check]) {
+    for check in checks {
+        let tag = match check.outcome {
+            Outcome::Pass => "PASS",
+            Outcome::Fail => "FAIL",
+            Outcome::Skip => "SKIP",
+        };
+        println!("[{}] {:<20} {}", tag, check.name, check.detail);
+        if let Some(fix) = &check.fix {
+            println!("       {:<20} fix: {}", "", fix);
+        }
+    }
+}
@@ -0,0 +1,109 @@
+//! Failure classes of `bridge-ctl`, each with its own exit code so scripts
+//! can tell a typo from a stopped bridge or a refused command.
+//!
+//! | Exit | Meaning |
+//! |------|---------|
+//! | 0 | OK |
+//! | 1 | Other error (e.g. unreadable reply) |
+//! | 2 | Invalid arguments (same as clap's usage errors) |
+//! | 3 | Bridge (or api-server) not reachable |
+//! | 4 | No GUI connection or session to inject with |
+//! | 5 | Command rejected by validation |
+//! | 6 | `doctor` found problems |
+
+use std::fmt;
+
+use serde::Serialize;
+use tcp_bridge::commands::CommandError;
+
+#[derive(Debug)]
+pub enum CtlError {
+    Invalid(String),
+    Unreachable(String),
+    NoSession(CommandError),
+    Rejected(CommandError),
+    /// `doctor` printed its report; this only carries the summary
+    ChecksFailed(String),
+    Other(String),
+}
+
+/// `--json` error document
+#[derive(Serialize)]
+struct ErrorOutput<'a> {
+    error: ErrorBody<'a>,
+    exit_code: u8,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: String,
+}
+
+impl CtlError {
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            Self::Other(_) => 1,
+            Self::Invalid(_) => 2,
+            Self::Unreachable(_) => 3,
+            Self::NoSession(_) => 4,
+            Self::Rejected(_) => 5,
+            Self::ChecksFailed(_) => 6,
+        }
+    }
+
+    /// `CommandError` code for bridge errors, otherwise the failure class
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::Other(_) => "error",
+            Self::Invalid(_) => "invalid_argument",
+            Self::Unreachable(_) => "unreachable",
+            Self::ChecksFailed(_) => "checks_failed",
+            Self::NoSession(e) | Self::Rejected(e) => e.code(),
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Self::Invalid(msg) | Self::Unreachable(msg) | Self::ChecksFailed(msg) | Self::Other(msg) => msg.clone(),
+            Self::NoSession(e) | Self::Rejected(e) => e.to_string(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        let output = ErrorOutput {
+            error: ErrorBody { code: self.code(), message: self.message() },
+            exit_code: self.exit_code(),
+        };
+        serde_json::to_string_pretty(&output).unwrap_or_default()
+    }
+}
+
+impl From<CommandError> for CtlError {
+    fn from(e: CommandError) -> Self {
+        if e.is_session_error() { Self::NoSession(e) } else { Self::Rejected(e) }
+    }
+}
+
+impl From<serde_json::Error> for CtlError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Other(format!("Invalid reply from bridge: {}", e))
+    }
+}
+
+impl From<std::io::Error> for CtlError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Other(e.to_string())
+    }
+}
+
+impl fmt::Display for CtlError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NoSession(e) | Self::Rejected(e) => write!(f, "{} [{}]", e, e.code()),
+            _ => write!(f, "{}", self.message()),
+        }
+    }
+}
+
+impl std::error::Error for CtlError {}
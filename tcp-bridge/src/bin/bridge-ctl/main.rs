@@ -0,0 +1,648 @@
+//! CLI for controlling the Rodecaster via the proxy.
+//! Uses human-readable names and unified commands.
+//!
+//! Talks to the bridge's control socket, or with `--remote` (or
+//! `BRIDGE_CTL_REMOTE`) to an `api-server` over HTTP with the same commands.
+//! `--json` prints machine-readable documents; exit codes are in `error.rs`.
+//! `--dry-run` has the bridge render a command's frames without sending it.
+//! The socket path, `doctor` addresses and fade pacing come from the shared
+//! config file (see `tcp_bridge::config`).
+
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::Ipv4Addr;
+use std::path::PathBuf;
+use std::process::ExitCode;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::UnixStream;
+
+use tcp_bridge::commands::{Command, CommandReply, CommandWarning, MixAction, ReloadReply, RenderReply};
+use tcp_bridge::config::Config;
+use tcp_bridge::faders::{FaderStatus, LEVEL_MAX};
+use tcp_bridge::names::{MixOutput, Source, Fader};
+use tcp_bridge::routing::{MatrixSnapshot, MixState, RoutingReply};
+use tcp_bridge::status::BridgeStatus;
+
+mod doctor;
+mod error;
+mod probe;
+mod script;
+mod tui;
+mod wire;
+
+use error::CtlError;
+
+#[derive(Parser, Debug)]
+#[command(author, version, about = "Rodecaster CLI control")]
+struct Args {
+    /// Send commands to an api-server instead of the local socket, e.g. http://rodecaster:8080
+    #[arg(long, global = true, env = "BRIDGE_CTL_REMOTE")]
+    remote: Option<String>,
+    /// Print JSON documents (replies, state, errors) instead of text
+    #[arg(long, global = true)]
+    json: bool,
+    /// Have the bridge validate and render the frames without sending them
+    #[arg(long, global = true)]
+    dry_run: bool,
+    /// Config file (default /etc/bridge.toml if it exists, or BRIDGE_CONFIG)
+    #[arg(long, global = true)]
+    config: Option<PathBuf>,
+    /// Override a config setting, e.g. --setting control.socket=/run/bridge.sock
+    #[arg(long = "setting", global = true, value_name = "SECTION.KEY=VALUE")]
+    overrides: Vec<String>,
+    /// The bridge's control socket
+    #[arg(long, global = true)]
+    socket: Option<PathBuf>,
+    #[command(subcommand)]
+    command: Commands,
+}
+
+/// Where commands are sent
+#[derive(Debug, Clone)]
+enum Target {
+    /// Control socket path
+    Socket(String),
+    /// Base URL of an api-server
+    Remote(String),
+}
+
+#[derive(Subcommand, Debug)]
+enum MixCommands {
+    /// Route a source and make it follow the fader (enables it first)
+    /// Example: mix link hp1 bluetooth
+    Link(MixCell),
+    /// Give a routed source a fixed level in the mix
+    Unlink(MixCell),
+    /// Stop routing a source to the mix
+    Disable(MixCell),
+    /// Route a disabled source again, keeping its link mode
+    Enable(MixCell),
+    /// Make one bus route exactly like another
+    /// Example: mix copy hp1 hp3
+    Copy {
+        /// Bus to copy from
+        from: String,
+        /// Bus to change
+        to: String,
+    },
+    /// Exchange the routing of two buses
+    /// Example: mix swap hp1 hp2
+    Swap {
+        first: String,
+        second: String,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct MixCell {
+    /// Mix output (hp1-4, speaker, recording, bt, usb1, usb2, chat, cm1-3)
+    mix: String,
+    /// Source (combo1-4, combo12/23/34, usb1/2, bt, pad, game, music, va, vb, cm1-3)
+    source: String,
+}
+
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Mix routing: link/unlink/disable/enable sources, copy/swap buses
+    /// Example: mix link hp1 bluetooth
+    Mix {
+        #[command(subcommand)]
+        action: MixCommands,
+    },
+    /// Give each remote a mix-minus: everything except itself on its return bus
+    /// Example: mix-minus chat cm1 cm2
+    MixMinus {
+        /// Remote sources (chat, usb2, cm1-3)
+        #[arg(required = true)]
+        remotes: Vec<String>,
+    },
+    /// Declare the routing of a whole bus; only differing cells are sent
+    /// Example: bus hp2 combo2=linked usb1=unlinked pad=disabled '*=disabled'
+    Bus {
+        /// Mix output (hp1-4, speaker, recording, bt, usb1, usb2, chat, cm1-3)
+        mix: String,
+        /// <source>=<linked|unlinked|disabled>; `*` matches every source not listed
+        #[arg(required = true)]
+        routes: Vec<String>,
+    },
+    /// Mute/unmute a fader
+    Mute {
+        /// Fader (p1-6, v1-3)
+        fader: String,
+        /// 0=unmute, 1=mute
+        #[arg(default_value_t = 1)]
+        state: u8,
+    },
+    /// Set fader level (0-65535)
+    Level {
+        /// Fader (p1-6, v1-3)
+        fader: String,
+        /// Level value
+        level: u32,
+    },
+    /// Simulate screen touch
+    Touch,
+    /// Show bridge status (session, connections, mixer health)
+    Status,
+    /// Show the routing matrix the bridge has observed (sources x mixes)
+    Matrix,
+    /// Show mute, source and level of each fader as the bridge has observed them
+    Faders,
+    /// Make the bridge re-read its config and spec file (same as SIGHUP)
+    Reload,
+    /// Full-screen view of faders and routing with keyboard control
+    Tui,
+    /// Walk faders x raw source IDs, inject each and record what the GUI shows
+    /// Example: probe --faders 0-1 --sources 0-16 --output mappings.md
+    Probe {
+        /// Fader indices (0-8): <n> or <from>-<to>
+        #[arg(long, default_value = "0-8")]
+        faders: String,
+        /// Raw channelInputSource IDs: <n> or <from>-<to>
+        #[arg(long, default_value = "0-16")]
+        sources: String,
+        /// Mapping table to write (.json for JSON, otherwise Markdown)
+        #[arg(long, short, default_value = "source_mappings.md")]
+        output: PathBuf,
+        /// How long to wait for the mixer's channelInputSource echo
+        #[arg(long, default_value_t = 2000)]
+        ack_timeout_ms: u64,
+    },
+    /// Run a file of commands with waits, fades, labels, loops and variables
+    /// Example: run show.txt --set bus=hp2 --dry-run
+    Run {
+        /// Script (.txt: one statement per line or `;`-separated; .yaml/.yml: `vars` and `steps`)
+        file: PathBuf,
+        /// Define a variable, overriding `set` in the file
+        #[arg(long = "set", value_name = "NAME=VALUE")]
+        vars: Vec<String>,
+        /// Keep running after a failed step (exit code is that of the first failure)
+        #[arg(long)]
+        keep_going: bool,
+    },
+    /// Check the deployment on the device: IP alias, DNAT rule, proxy, GUI, session, traffic
+    Doctor {
+        /// Address the proxy listens on (default from the config)
+        #[arg(long)]
+        bind_ip: Option<Ipv4Addr>,
+        #[arg(long)]
+        bind_port: Option<u16>,
+        /// Mixer address the GUI connects to
+        #[arg(long)]
+        target_ip: Option<Ipv4Addr>,
+        #[arg(long)]
+        target_port: Option<u16>,
+        /// Address the proxy connects to the mixer from (excluded from the DNAT rule)
+        #[arg(long)]
+        source_ip: Option<Ipv4Addr>,
+        /// Longest time without a frame in either direction before failing
+        #[arg(long, default_value_t = 10)]
+        max_idle_secs: u64,
+    },
+    /// Print the frames a command would inject, without a bridge
+    /// Example: encode --session 0a0b0c0d mix link hp1 pad
+    Encode {
+        /// Session ID (4 hex bytes); a placeholder is used if not given
+        #[arg(long)]
+        session: Option<String>,
+        /// CallMe session ID (4 hex bytes), needed for CallMe routing
+        #[arg(long)]
+        callme_session: Option<String>,
+        /// Spec file for `spec` commands (default from the config)
+        #[arg(long)]
+        spec: Option<PathBuf>,
+        /// Subcommand and its arguments, e.g. `mute p1`
+        #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+        command: Vec<String>,
+    },
+    /// Split hex frames into their fields (reads stdin if no frames are given)
+    /// Example: decode 2c9eb4f21a000000...
+    Decode {
+        /// Hex bytes: whole frames (one or more) or a bare payload
+        hex: Vec<String>,
+    },
+    /// Send a command defined in the bridge's spec file
+    /// Example: spec mute 0 1
+    Spec {
+        /// Command name from the spec file
+        name: String,
+        /// Index (added to the command's index base)
+        index: u8,
+        /// Value (optional if the spec defines a default)
+        #[arg(allow_hyphen_values = true)]
+        value: Option<i64>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    let args = Args::parse();
+    let json = args.json;
+    match run(args).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            match e {
+                // The doctor report already says what failed
+                CtlError::ChecksFailed(_) if json => {}
+                _ if json => println!("{}", e.to_json()),
+                _ => eprintln!("Error: {}", e),
+            }
+            ExitCode::from(e.exit_code())
+        }
+    }
+}
+
+/// `--json` document for a command that was sent
+#[derive(Serialize)]
+struct SentOutput<'a> {
+    sent: &'a Command,
+    warnings: &'a [CommandWarning],
+}
+
+async fn run(args: Args) -> Result<(), CtlError> {
+    let config = Config::load(args.config.as_deref(), &args.overrides).map_err(CtlError::Invalid)?;
+    let socket = args.socket.unwrap_or(config.control.socket);
+    let target = match args.remote {
+        Some(url) if url.starts_with("http://") => Target::Remote(url.trim_end_matches('/').to_string()),
+        Some(url) => return Err(CtlError::Invalid(format!("Invalid remote: {} (expected http://host:port)", url))),
+        None => Target::Socket(socket.to_string_lossy().into_owned()),
+    };
+    let target = &target;
+    let json = args.json;
+
+    if let Some(cmd) = to_command(&args.command)? {
+        if args.dry_run {
+            let reply = render(target, &cmd).await?;
+            return print_render(&cmd, &reply, json);
+        }
+        return send(target, &cmd, json).await;
+    }
+    if args.dry_run && !matches!(args.command, Commands::Run { .. }) {
+        return Err(CtlError::Invalid("--dry-run only applies to commands that send something".to_string()));
+    }
+    match args.command {
+        Commands::Matrix => {
+            let matrix: MatrixSnapshot = serde_json::from_str(&query(target, &Command::Matrix).await?)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&matrix)?);
+            } else {
+                print_matrix(&matrix);
+            }
+        }
+        Commands::Faders => {
+            let faders: Vec<FaderStatus> = serde_json::from_str(&query(target, &Command::Faders).await?)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&faders)?);
+            } else {
+                print_faders(&faders);
+            }
+        }
+        Commands::Status => {
+            let status: BridgeStatus = serde_json::from_str(&query(target, &Command::Status).await?)?;
+            if json {
+                println!("{}", serde_json::to_string_pretty(&status)?);
+            } else {
+                print_status(&status);
+            }
+        }
+        Commands::Reload => {
+            let reply: ReloadReply = serde_json::from_str(&query(target, &Command::Reload).await?)?;
+            if let Some(e) = reply.error {
+                return Err(CtlError::Other(format!("Reload failed: {}", e)));
+            }
+            if json {
+                println!("{}", serde_json::to_string_pretty(&reply)?);
+            } else {
+                print_reload(&reply);
+            }
+        }
+        Commands::Tui => tui::run(target).await?,
+        Commands::Probe { faders, sources, output, ack_timeout_ms } => {
+            let probe = probe::Probe {
+                faders: probe::parse_range(&faders)?,
+                sources: probe::parse_range(&sources)?,
+                output,
+                ack_timeout: Duration::from_millis(ack_timeout_ms),
+                json,
+            };
+            probe.run(target).await?;
+        }
+        Commands::Run { file, vars, keep_going } => {
+            let script = script::Script::load(&file, &vars)?;
+            let fade_step = Duration::from_millis(config.pacing.fade_step_ms.max(1));
+            script.run(target, script::Options { keep_going, dry_run: args.dry_run, json, fade_step }).await?;
+        }
+        Commands::Encode { session, callme_session, spec, command } => {
+            let cmd = to_command(&parse_words(&command)?)?
+                .ok_or_else(|| CtlError::Invalid("Only commands that send something can be encoded".to_string()))?;
+            let spec = spec.unwrap_or(config.proxy.spec);
+            let reply = wire::encode(&cmd, session.as_deref(), callme_session.as_deref(), &spec)?;
+            wire::print_encoded(&reply, json)?;
+        }
+        Commands::Decode { hex } => wire::decode(&hex, json)?,
+        Commands::Doctor { bind_ip, bind_port, target_ip, target_port, source_ip, max_idle_secs } => {
+            let mut network = config.proxy.network_setup().map_err(CtlError::Invalid)?;
+            network.bind_ip = bind_ip.unwrap_or(network.bind_ip);
+            network.bind_port = bind_port.unwrap_or(network.bind_port);
+            network.target_ip = target_ip.unwrap_or(network.target_ip);
+            network.target_port = target_port.unwrap_or(network.target_port);
+            network.source_ip = source_ip.unwrap_or(network.source_ip);
+            let setup = doctor::Setup { network, max_idle_ms: max_idle_secs * 1000 };
+            doctor::run(target, &setup, json).await?;
+        }
+        _ => unreachable!("handled by to_command"),
+    }
+    Ok(())
+}
+
+/// The bridge command a subcommand sends; `None` for queries and tools
+fn to_command(command: &Commands) -> Result<Option<Command>, CtlError> {
+    Ok(Some(match command {
+        Commands::Mix { action } => {
+            let (action, cell) = match action {
+                MixCommands::Link(cell) => (MixAction::Link, cell),
+                MixCommands::Unlink(cell) => (MixAction::Unlink, cell),
+                MixCommands::Disable(cell) => (MixAction::Disable, cell),
+                MixCommands::Enable(cell) => (MixAction::Enable, cell),
+                MixCommands::Copy { from, to } => {
+                    return Ok(Some(Command::MixCopy { from: parse_mix(from)?, to: parse_mix(to)? }));
+                }
+                MixCommands::Swap { first, second } => {
+                    return Ok(Some(Command::MixSwap { first: parse_mix(first)?, second: parse_mix(second)? }));
+                }
+            };
+            Command::Mix { action, mix: parse_mix(&cell.mix)?, source: parse_source(&cell.source)? }
+        }
+        Commands::MixMinus { remotes } => {
+            let remotes = remotes.iter()
+                .map(|r| parse_source(r))
+                .collect::<Result<Vec<Source>, _>>()?;
+            Command::MixMinus { remotes }
+        }
+        Commands::Bus { mix, routes } => Command::Bus { mix: parse_mix(mix)?, routes: parse_routes(routes)? },
+        Commands::Mute { fader, state } => {
+            Command::Fader { fader: parse_fader(fader)?, muted: Some(*state != 0), source: None, level: None }
+        }
+        Commands::Level { fader, level } => {
+            let level = Some((*level).min(LEVEL_MAX) as f32 / LEVEL_MAX as f32);
+            Command::Fader { fader: parse_fader(fader)?, muted: None, source: None, level }
+        }
+        Commands::Touch => Command::Touch,
+        Commands::Spec { name, index, value } => Command::Spec { name: name.clone(), index: *index, value: *value },
+        Commands::Matrix | Commands::Faders | Commands::Status | Commands::Reload
+        | Commands::Tui | Commands::Probe { .. } | Commands::Run { .. }
+        | Commands::Encode { .. } | Commands::Decode { .. } | Commands::Doctor { .. } => return Ok(None),
+    }))
+}
+
+/// Parse a subcommand given as words (scripts, `encode`)
+fn parse_words(words: &[String]) -> Result<Commands, CtlError> {
+    let args = Args::try_parse_from(std::iter::once("bridge-ctl").chain(words.iter().map(String::as_str)))
+        .map_err(|e| {
+            let msg = e.to_string();
+            let first_line = msg.lines().next().unwrap_or_default();
+            CtlError::Invalid(first_line.trim_start_matches("error: ").to_string())
+        })?;
+    Ok(args.command)
+}
+
+/// Validate, send and print the reply like the matching subcommand
+async fn send(target: &Target, cmd: &Command, json: bool) -> Result<(), CtlError> {
+    if cmd.is_routing() {
+        return send_routing(target, cmd, json).await;
+    }
+    let reply = submit(target, cmd).await?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&SentOutput { sent: cmd, warnings: &reply.warnings })?);
+    } else {
+        println!("Sent: {}", serde_json::to_string(cmd)?);
+        print_warnings(&reply.warnings);
+    }
+    Ok(())
+}
+
+/// Validate and send a non-routing command, turning a rejection into an error
+async fn submit(target: &Target, cmd: &Command) -> Result<CommandReply, CtlError> {
+    cmd.validate()?;
+    let reply: CommandReply = serde_json::from_str(&query(target, cmd).await?)?;
+    match reply.error {
+        Some(e) => Err(e.into()),
+        None => Ok(reply),
+    }
+}
+
+/// Ask the bridge for the frames a command would inject
+async fn render(target: &Target, cmd: &Command) -> Result<RenderReply, CtlError> {
+    cmd.validate()?;
+    let render = Command::Render { command: Box::new(cmd.clone()) };
+    let reply: RenderReply = serde_json::from_str(&query(target, &render).await?)?;
+    match reply.error {
+        Some(e) => Err(e.into()),
+        None => Ok(reply),
+    }
+}
+
+fn print_render(cmd: &Command, reply: &RenderReply, json: bool) -> Result<(), CtlError> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(reply)?);
+        return Ok(());
+    }
+    if !reply.session_known {
+        eprintln!("Note: no session sniffed yet; frames use session {}", reply.session_id);
+    }
+    println!("Would send: {}", serde_json::to_string(cmd)?);
+    for frame in &reply.frames {
+        println!("  {}", frame);
+    }
+    if reply.frames.is_empty() {
+        println!("  (nothing to send)");
+    }
+    print_warnings(&reply.warnings);
+    Ok(())
+}
+
+/// Send a command and read the bridge's reply
+async fn query(target: &Target, cmd: &Command) -> Result<String, CtlError> {
+    let json = serde_json::to_string(cmd)?;
+    match target {
+        Target::Socket(path) => {
+            let mut stream = UnixStream::connect(path).await
+                .map_err(|e| CtlError::Unreachable(format!("Cannot connect to {}: {} (is tcp-bridge running?)", path, e)))?;
+            stream.write_all(json.as_bytes()).await?;
+            // Signal end of request so the bridge can parse it
+            stream.shutdown().await?;
+
+            let mut reply = String::new();
+            stream.read_to_string(&mut reply).await?;
+            Ok(reply)
+        }
+        Target::Remote(url) => {
+            // api-server passes the bridge's reply through unchanged
+            let response = reqwest::Client::new()
+                .post(format!("{}/command", url))
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .body(json)
+                .send().await
+                .map_err(|e| CtlError::Unreachable(format!("Cannot reach {}: {}", url, e)))?;
+            let status = response.status();
+            let body = response.text().await
+                .map_err(|e| CtlError::Other(format!("Reading reply from {}: {}", url, e)))?;
+            if !status.is_success() {
+                let error = serde_json::from_str::<serde_json::Value>(&body).ok()
+                    .and_then(|v| v["error"].as_str().map(str::to_string))
+                    .unwrap_or(body);
+                let error = format!("{} (HTTP {})", error.trim(), status.as_u16());
+                // 503: api-server is up but the bridge is not
+                return Err(match status {
+                    reqwest::StatusCode::SERVICE_UNAVAILABLE => CtlError::Unreachable(error),
+                    _ => CtlError::Other(error),
+                });
+            }
+            Ok(body)
+        }
+    }
+}
+
+/// Parse `source=state` pairs, checking source names up front
+fn parse_routes(routes: &[String]) -> Result<BTreeMap<String, MixState>, CtlError> {
+    routes.iter().map(|route| {
+        let (source, state) = route.split_once('=')
+            .ok_or_else(|| CtlError::Invalid(format!("Invalid route: {} (expected <source>=<state>)", route)))?;
+        let source = source.trim();
+        if source != "*" {
+            parse_source(source)?;
+        }
+        Ok((source.to_string(), state.trim().parse().map_err(CtlError::Invalid)?))
+    }).collect()
+}
+
+fn parse_mix(mix: &str) -> Result<MixOutput, CtlError> {
+    mix.parse().map_err(|e| CtlError::Invalid(format!("Invalid mix: {}", e)))
+}
+
+fn parse_source(source: &str) -> Result<Source, CtlError> {
+    source.parse().map_err(|e| CtlError::Invalid(format!("Invalid source: {}", e)))
+}
+
+fn parse_fader(fader: &str) -> Result<Fader, CtlError> {
+    fader.parse().map_err(|e| CtlError::Invalid(format!("Invalid fader: {}", e)))
+}
+
+fn print_warnings(warnings: &[CommandWarning]) {
+    for warning in warnings {
+        eprintln!("Warning: {} [{}]", warning, warning.code());
+    }
+}
+
+/// Send a bus/copy/swap request and print the cells the bridge changed
+async fn send_routing(target: &Target, cmd: &Command, json: bool) -> Result<(), CtlError> {
+    cmd.validate()?;
+    let reply: RoutingReply = serde_json::from_str(&query(target, cmd).await?)?;
+    if let Some(e) = reply.error {
+        return Err(e.into());
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reply)?);
+        return Ok(());
+    }
+
+    for bus in &reply.buses {
+        if bus.changed.is_empty() {
+            println!("{}: already up to date", bus.mix);
+        }
+        for change in &bus.changed {
+            let from = change.from.map_or("unknown".to_string(), |s| s.to_string());
+            println!("{} <- {}: {} -> {}", bus.mix, change.source, from, change.to);
+        }
+        for skipped in &bus.skipped {
+            println!("{}: skipped {}", bus.mix, skipped);
+        }
+    }
+    print_warnings(&reply.warnings);
+    Ok(())
+}
+
+fn print_faders(faders: &[FaderStatus]) {
+    println!("{:<6}{:<12}{:<8}Level", "Fader", "Source", "Mute");
+    for f in faders {
+        let source = match (f.source, f.source_id) {
+            (Some(source), _) => source.to_string(),
+            (None, Some(id)) => format!("#{}", id),
+            (None, None) => "?".to_string(),
+        };
+        let mute = match f.muted {
+            Some(true) => "muted",
+            Some(false) => "on",
+            None => "?",
+        };
+        let level = f.level.map_or("?".to_string(), |l| format!("{} ({}%)", l, l * 100 / LEVEL_MAX));
+        println!("{:<6}{:<12}{:<8}{}", f.fader.short_name(), source, mute, level);
+    }
+    println!("? = not seen yet");
+}
+
+fn print_matrix(matrix: &MatrixSnapshot) {
+    print!("{:<10}", "");
+    for mix in MixOutput::ALL {
+        print!("{:>5}", mix.short_name());
+    }
+    println!();
+    for source in Source::ALL {
+        print!("{:<10}", source.to_string());
+        for mix in MixOutput::ALL {
+            let marker = match matrix.get(mix, source) {
+                Some(MixState::Linked) => "L",
+                Some(MixState::Unlinked) => "U",
+                Some(MixState::Disabled) => "-",
+                None => "?",
+            };
+            print!("{:>5}", marker);
+        }
+        println!();
+    }
+    println!("L = linked, U = unlinked (fixed level), - = disabled, ? = not seen yet");
+}
+
+fn print_reload(reply: &ReloadReply) {
+    println!("Reloaded ({} command specs)", reply.specs);
+    let groups = [
+        ("Applied", &reply.applied),
+        ("Needs a restart", &reply.restart_required),
+        ("Not used by the bridge", &reply.not_used),
+    ];
+    if groups.iter().all(|(_, settings)| settings.is_empty()) {
+        println!("No settings changed");
+    }
+    for (label, settings) in groups {
+        if !settings.is_empty() {
+            println!("{:<24}{}", format!("{}:", label), settings.join(", "));
+        }
+    }
+}
+
+fn print_status(status: &BridgeStatus) {
+    let up = &status.upstream;
+    println!("Connections: {}", status.connections);
+    println!("Session ID:  {}", status.session_id.as_deref().unwrap_or("(not sniffed yet)"));
+    println!("CallMe ID:   {}", status.callme_session_id.as_deref().unwrap_or("(not observed yet)"));
+    if let Some(change) = &status.last_session_change {
+        println!("Changes:     {} (last: {} -> {})",
+            status.session_changes, change.old.as_deref().unwrap_or("-"), change.new);
+    }
+    if let (Some(to), Some(from)) = (status.last_to_mixer_ms, status.last_from_mixer_ms) {
+        println!("Last frame:  {} ms ago to mixer, {} ms ago from mixer", to, from);
+    }
+    println!("Mixer:       {}", if up.healthy { "healthy" } else { "NOT ANSWERING PINGS" });
+    println!("Pings:       {} sent, {} answered", up.pings_sent, up.pings_answered);
+    if let Some(latency) = up.latency_ms {
+        println!("Latency:     {} ms", latency);
+    }
+    if let Some(gap) = up.ping_gap_ms {
+        println!("Ping gap:    {} ms", gap);
+    }
+    if let Some(waiting) = up.waiting_ms {
+        println!("Waiting:     {} ms for reply", waiting);
+    }
+}
@@ -0,0 +1,213 @@
+//! Interactive source-mapping probe.
+//!
+//! Injects each raw source ID on each fader (with the `inputMicrophoneType`
+//! UI fix), watches the bridge's traffic log for the mixer's
+//! `channelInputSource` echo and asks the operator what the GUI shows.
+//! The result is written as a table that can be used to update `names.rs`
+//! and the Source IDs list in the protocol notes.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::ops::RangeInclusive;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, BufReader, Lines, Stdin};
+
+use tcp_bridge::commands::{Command, CommandReply};
+use tcp_bridge::names::Fader;
+use tcp_bridge::protocol::{ChannelInputSource, RodeDecode};
+use tcp_bridge::traffic::{Direction, TrafficSnapshot};
+
+use crate::error::CtlError;
+use crate::{Target, query};
+
+const POLL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Serialize)]
+struct ProbeResult {
+    fader: Fader,
+    source_id: u32,
+    /// What the operator saw on screen (empty = nothing)
+    label: String,
+    /// Source ID in the mixer's `channelInputSource` echo, if one came
+    acknowledged: Option<u32>,
+    /// Other properties the mixer sent while we waited
+    mixer_replies: Vec<String>,
+}
+
+pub struct Probe {
+    pub faders: RangeInclusive<u8>,
+    pub sources: RangeInclusive<u32>,
+    pub output: PathBuf,
+    pub ack_timeout: Duration,
+    pub json: bool,
+}
+
+/// Parse `a-b` or a single number
+pub fn parse_range<T: std::str::FromStr + PartialOrd + Copy>(spec: &str) -> Result<RangeInclusive<T>, CtlError> {
+    let invalid = || CtlError::Invalid(format!("Invalid range: {} (expected <n> or <from>-<to>)", spec));
+    let (from, to) = spec.split_once('-').unwrap_or((spec, spec));
+    let from: T = from.trim().parse().map_err(|_| invalid())?;
+    let to: T = to.trim().parse().map_err(|_| invalid())?;
+    if from > to {
+        return Err(invalid());
+    }
+    Ok(from..=to)
+}
+
+impl Probe {
+    pub async fn run(self, target: &Target) -> Result<(), CtlError> {
+        let faders = self.faders.clone()
+            .map(|i| Fader::from_index(i).ok_or_else(|| CtlError::Invalid(format!("No fader with index {} (0-8)", i))))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut input = BufReader::new(tokio::io::stdin()).lines();
+        let mut results = Vec::new();
+
+        println!("Probing faders {:?} x source IDs {:?}", self.faders, self.sources);
+        println!("Each step sets the source ID, then sends the mic type UI fix (-1, 4).");
+        let outcome = self.walk(target, &faders, &mut input, &mut results).await;
+
+        if !results.is_empty() {
+            self.write(&results)?;
+        }
+        outcome
+    }
+
+    async fn walk(
+        &self,
+        target: &Target,
+        faders: &[Fader],
+        input: &mut Lines<BufReader<Stdin>>,
+        results: &mut Vec<ProbeResult>,
+    ) -> Result<(), CtlError> {
+        for &fader in faders {
+            println!("\n### Fader {} (index {})", fader.short_name(), fader.to_index());
+            for source_id in self.sources.clone() {
+                let answer = prompt(input, &format!(
+                    "{} <- source {}: [Enter] apply, s skip, n next fader, q finish: ",
+                    fader.short_name(), source_id
+                )).await?;
+                match answer.as_deref().map(str::trim) {
+                    None | Some("q") => return Ok(()),
+                    Some("s") => continue,
+                    Some("n") => break,
+                    _ => {}
+                }
+
+                let since = traffic(target, Some(u64::MAX)).await?.next_seq;
+                let reply: CommandReply = serde_json::from_str(
+                    &query(target, &Command::RawSource { fader, source_id }).await?
+                )?;
+                match reply.error.map(CtlError::from) {
+                    Some(e @ CtlError::Rejected(_)) => {
+                        eprintln!("Error: {}", e);
+                        continue;
+                    }
+                    // Session problems repeat on every step
+                    Some(e) => return Err(e),
+                    None => {}
+                }
+
+                let (acknowledged, mixer_replies) = self.wait_for_ack(target, fader, since).await?;
+                match acknowledged {
+                    Some(id) if id == source_id => println!("Mixer acknowledged source {}", id),
+                    Some(id) => println!("Mixer answered with source {} (sent {})", id, source_id),
+                    None => println!("No channelInputSource echo within {} ms", self.ack_timeout.as_millis()),
+                }
+                if !mixer_replies.is_empty() {
+                    println!("Mixer also sent: {}", mixer_replies.join(", "));
+                }
+
+                let Some(label) = prompt(input, "What appeared on screen? (empty = nothing): ").await? else {
+                    return Ok(());
+                };
+                results.push(ProbeResult {
+                    fader,
+                    source_id,
+                    label: label.trim().to_string(),
+                    acknowledged,
+                    mixer_replies,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Poll the traffic log for the mixer's `channelInputSource` on this fader
+    async fn wait_for_ack(&self, target: &Target, fader: Fader, mut since: u64) -> Result<(Option<u32>, Vec<String>), CtlError> {
+        let deadline = Instant::now() + self.ack_timeout;
+        let mut replies = Vec::new();
+        while Instant::now() < deadline {
+            let snapshot = traffic(target, Some(since)).await?;
+            since = snapshot.next_seq;
+            for entry in snapshot.entries.iter().filter(|e| e.direction == Direction::FromMixer) {
+                let ack = hex::decode(&entry.payload).ok()
+                    .and_then(|payload| ChannelInputSource::decode_payload(&payload))
+                    .filter(|ack| ack.fader_index == fader.to_index());
+                if let Some(ack) = ack {
+                    return Ok((Some(ack.source_id), replies));
+                }
+                if let Some(property) = &entry.property
+                    && !replies.contains(property)
+                {
+                    replies.push(property.clone());
+                }
+            }
+            tokio::time::sleep(POLL).await;
+        }
+        Ok((None, replies))
+    }
+
+    fn write(&self, results: &[ProbeResult]) -> Result<(), CtlError> {
+        let is_json = self.output.extension().is_some_and(|e| e == "json");
+        let table = if is_json { serde_json::to_string_pretty(results)? } else { markdown(results) };
+        std::fs::write(&self.output, table)
+            .map_err(|e| CtlError::Other(format!("Cannot write {}: {}", self.output.display(), e)))?;
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(results)?);
+        } else {
+            println!("\n{}", by_source(results));
+            println!("Wrote {} results to {}", results.len(), self.output.display());
+        }
+        Ok(())
+    }
+}
+
+async fn traffic(target: &Target, since: Option<u64>) -> Result<TrafficSnapshot, CtlError> {
+    Ok(serde_json::from_str(&query(target, &Command::Traffic { since }).await?)?)
+}
+
+/// Print a question and read one line; `None` on end of input
+async fn prompt(input: &mut Lines<BufReader<Stdin>>, question: &str) -> Result<Option<String>, CtlError> {
+    print!("{}", question);
+    std::io::stdout().flush()?;
+    Ok(input.next_line().await?)
+}
+
+fn markdown(results: &[ProbeResult]) -> String {
+    let mut out = String::from("# Source ID probe\n\n");
+    out.push_str("| Source ID | Fader | On screen | Mixer ack |\n| :--- | :--- | :--- | :--- |\n");
+    for r in results {
+        let ack = r.acknowledged.map_or("-".to_string(), |id| id.to_string());
+        out.push_str(&format!("| {} | {} | {} | {} |\n", r.source_id, r.fader.short_name(), r.label, ack));
+    }
+    out.push_str("\n## Source IDs\n");
+    out.push_str(&by_source(results));
+    out
+}
+
+/// One line per source ID in the format of the protocol notes
+fn by_source(results: &[ProbeResult]) -> String {
+    let mut labels: BTreeMap<u32, Vec<&str>> = BTreeMap::new();
+    for r in results.iter().filter(|r| !r.label.is_empty()) {
+        let seen = labels.entry(r.source_id).or_default();
+        if !seen.contains(&r.label.as_str()) {
+            seen.push(&r.label);
+        }
+    }
+    labels.iter()
+        .map(|(id, labels)| format!("*   **{}**: {}\n", id, labels.join(" / ")))
+        .collect()
+}
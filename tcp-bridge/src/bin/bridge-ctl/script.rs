@@ -0,0 +1,611 @@
+//! `bridge-ctl run`: command files with waits, fades, labels, loops and variables.
+//!
+//! One statement per line (or several separated by `;`); `#` starts a comment:
+//!
+//! ```text
+//! set bus hp1              # variable, used as $bus or ${bus}
+//! label intro              # jump target (or `intro:`)
+//! mute p1
+//! wait 500ms               # ms, s or m
+//! fade v2 0 over 2s        # level 0-65535 or 0-100%; `from <level>` if not seen yet
+//! mix link $bus pad
+//! loop intro 3             # jump back 3 more times; without a count: forever
+//! ```
+//!
+//! Any other statement is a regular subcommand. YAML files hold the same
+//! statements: `{vars: {bus: hp1}, steps: ["mute p1", "wait 500ms"]}`.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use std::time::Duration;
+
+use tcp_bridge::commands::{Command, CommandWarning};
+use tcp_bridge::faders::{FaderStatus, LEVEL_MAX};
+
+use crate::error::CtlError;
+use crate::{Target, parse_fader, parse_words, print_warnings, query, render, send, submit, to_command};
+
+pub struct Options {
+    pub keep_going: bool,
+    pub dry_run: bool,
+    pub json: bool,
+    /// Interval between level updates while fading (`pacing.fade_step_ms`)
+    pub fade_step: Duration,
+}
+
+#[derive(Deserialize)]
+struct YamlScript {
+    #[serde(default)]
+    vars: BTreeMap<String, serde_yaml::Value>,
+    steps: Vec<String>,
+}
+
+/// `--json --dry-run` document per command
+#[derive(Serialize)]
+struct DryRunOutput<'a> {
+    line: usize,
+    statement: &'a str,
+    command: &'a Command,
+    frames: &'a [String],
+    warnings: &'a [CommandWarning],
+}
+
+struct Statement {
+    line: usize,
+    text: String,
+}
+
+pub struct Script {
+    statements: Vec<Statement>,
+    /// Label name -> statement index
+    labels: HashMap<String, usize>,
+    vars: HashMap<String, String>,
+    /// Variables from the command line; `set` does not change them
+    fixed: HashSet<String>,
+    /// Remaining jumps per `loop` statement
+    loops: HashMap<usize, u32>,
+}
+
+/// What a control statement (`set`, `label`, `loop`) does to the run
+#[derive(Debug, PartialEq)]
+enum Flow {
+    Next,
+    Jump(usize),
+    /// Not a control statement; the runner executes it
+    Run,
+}
+
+impl Script {
+    pub fn load(path: &Path, cli_vars: &[String]) -> Result<Self, CtlError> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| CtlError::Invalid(format!("Cannot read {}: {}", path.display(), e)))?;
+        Self::parse(&content, path, cli_vars)
+    }
+
+    /// Parse a script; `path` picks the YAML or text form and names it in errors
+    fn parse(content: &str, path: &Path, cli_vars: &[String]) -> Result<Self, CtlError> {
+        let mut vars = HashMap::new();
+        let mut statements = Vec::new();
+        if path.extension().is_some_and(|e| e == "yaml" || e == "yml") {
+            let yaml: YamlScript = serde_yaml::from_str(content)
+                .map_err(|e| CtlError::Invalid(format!("Invalid script {}: {}", path.display(), e)))?;
+            for (name, value) in yaml.vars {
+                vars.insert(name, yaml_scalar(&value)?);
+            }
+            for (i, step) in yaml.steps.iter().enumerate() {
+                statements.extend(split_statements(step, i + 1)?.into_iter().map(|text| Statement { line: i + 1, text }));
+            }
+        } else {
+            for (i, line) in content.lines().enumerate() {
+                statements.extend(split_statements(line, i + 1)?.into_iter().map(|text| Statement { line: i + 1, text }));
+            }
+        }
+
+        let mut fixed = HashSet::new();
+        for var in cli_vars {
+            let (name, value) = var.split_once('=')
+                .ok_or_else(|| CtlError::Invalid(format!("Invalid variable: {} (expected NAME=VALUE)", var)))?;
+            vars.insert(name.to_string(), value.to_string());
+            fixed.insert(name.to_string());
+        }
+
+        let mut labels = HashMap::new();
+        for (i, statement) in statements.iter().enumerate() {
+            if let Some(label) = label_name(&statement.text)
+                && labels.insert(label.to_string(), i).is_some()
+            {
+                return Err(CtlError::Invalid(format!("Line {}: duplicate label {}", statement.line, label)));
+            }
+        }
+        // Catch bad jumps before anything is sent
+        for statement in &statements {
+            let words = split_words(&statement.text)
+                .map_err(|e| CtlError::Invalid(format!("Line {}: {}", statement.line, e)))?;
+            if words.first().map(String::as_str) == Some("loop") {
+                match words.get(1) {
+                    Some(label) if label.contains('$') || labels.contains_key(label) => {}
+                    Some(label) => return Err(CtlError::Invalid(format!("Line {}: unknown label {}", statement.line, label))),
+                    None => return Err(CtlError::Invalid(format!("Line {}: usage: loop <label> [count]", statement.line))),
+                }
+            }
+        }
+
+        Ok(Script { statements, labels, vars, fixed, loops: HashMap::new() })
+    }
+
+    pub async fn run(mut self, target: &Target, options: Options) -> Result<(), CtlError> {
+        let mut runner = Runner { target, options, session_noted: false };
+        let mut first_error = None;
+        let mut failed = 0;
+        let mut executed = 0;
+
+        let mut pc = 0;
+        while pc < self.statements.len() {
+            let result = match self.step(pc, runner.options.dry_run) {
+                Ok((Flow::Next, _)) => Ok(None),
+                Ok((Flow::Jump(jump), _)) => Ok(Some(jump)),
+                Ok((Flow::Run, text)) => runner.execute(self.statements[pc].line, &text).await.map(|()| None),
+                Err(e) => Err(e),
+            };
+            executed += 1;
+            let statement = &self.statements[pc];
+            match result {
+                Ok(Some(jump)) => {
+                    pc = jump;
+                    continue;
+                }
+                Ok(None) => {}
+                Err(e) if runner.options.keep_going => {
+                    failed += 1;
+                    eprintln!("Line {} ({}): {}", statement.line, statement.text, e);
+                    first_error.get_or_insert(e);
+                }
+                Err(e) => {
+                    if !runner.options.json {
+                        eprintln!("Stopped at line {}: {}", statement.line, statement.text);
+                    }
+                    return Err(e);
+                }
+            }
+            pc += 1;
+        }
+
+        match first_error {
+            Some(e) => {
+                eprintln!("{} of {} steps failed", failed, executed);
+                Err(e)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Expand statement `pc` and run it if it is a control statement.
+    /// Returns the expanded text for the runner.
+    fn step(&mut self, pc: usize, dry_run: bool) -> Result<(Flow, String), CtlError> {
+        let text = self.expand(&self.statements[pc].text)?;
+        let words = split_words(&text)?;
+        let Some(first) = words.first() else { return Ok((Flow::Next, text)) };
+
+        let flow = match first.as_str() {
+            _ if label_name(&text).is_some() => Flow::Next,
+            "set" => {
+                let [_, name, value @ ..] = words.as_slice() else {
+                    return Err(CtlError::Invalid("Usage: set <name> <value>".to_string()));
+                };
+                if !self.fixed.contains(name) {
+                    self.vars.insert(name.clone(), value.join(" "));
+                }
+                Flow::Next
+            }
+            "loop" => {
+                let label = words.get(1).cloned().unwrap_or_default();
+                let target = *self.labels.get(&label)
+                    .ok_or_else(|| CtlError::Invalid(format!("Unknown label: {}", label)))?;
+                let Some(count) = words.get(2) else {
+                    if dry_run {
+                        eprintln!("[line {}] loops forever; shown once", self.statements[pc].line);
+                        return Ok((Flow::Next, text));
+                    }
+                    return Ok((Flow::Jump(target), text));
+                };
+                let count: u32 = count.parse()
+                    .map_err(|_| CtlError::Invalid(format!("Invalid loop count: {}", count)))?;
+                let remaining = self.loops.entry(pc).or_insert(count);
+                if *remaining > 0 {
+                    *remaining -= 1;
+                    Flow::Jump(target)
+                } else {
+                    // Done; an enclosing loop may run this one again
+                    self.loops.remove(&pc);
+                    Flow::Next
+                }
+            }
+            _ => Flow::Run,
+        };
+        Ok((flow, text))
+    }
+
+    /// Replace `$name` / `${name}`; `$$` is a literal `$`
+    fn expand(&self, text: &str) -> Result<String, CtlError> {
+        let mut out = String::new();
+        let mut chars = text.chars().peekable();
+        while let Some(c) = chars.next() {
+            if c != '$' {
+                out.push(c);
+                continue;
+            }
+            let name: String = match chars.peek() {
+                Some('$') => {
+                    chars.next();
+                    out.push('$');
+                    continue;
+                }
+                Some('{') => {
+                    chars.next();
+                    let name: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                    name
+                }
+                _ => {
+                    let mut name = String::new();
+                    while let Some(&c) = chars.peek()
+                        && (c.is_ascii_alphanumeric() || c == '_')
+                    {
+                        name.push(c);
+                        chars.next();
+                    }
+                    name
+                }
+            };
+            let value = self.vars.get(&name)
+                .ok_or_else(|| CtlError::Invalid(format!("Undefined variable: ${}", name)))?;
+            out.push_str(value);
+        }
+        Ok(out)
+    }
+}
+
+struct Runner<'a> {
+    target: &'a Target,
+    options: Options,
+    session_noted: bool,
+}
+
+impl Runner<'_> {
+    /// Run one expanded statement that is not a control statement
+    async fn execute(&mut self, line: usize, text: &str) -> Result<(), CtlError> {
+        let words = split_words(text)?;
+        let Some(first) = words.first() else { return Ok(()) };
+
+        match first.as_str() {
+            "wait" => {
+                let [_, duration] = words.as_slice() else {
+                    return Err(CtlError::Invalid("Usage: wait <duration>".to_string()));
+                };
+                let duration = parse_duration(duration)?;
+                if self.options.dry_run {
+                    if !self.options.json {
+                        println!("[line {}] wait {} ms", line, duration.as_millis());
+                    }
+                } else {
+                    tokio::time::sleep(duration).await;
+                }
+            }
+            "fade" => self.fade(&words, line, text).await?,
+            _ => {
+                let cmd = to_command(&parse_words(&words)?)?
+                    .ok_or_else(|| CtlError::Invalid(format!("`{}` cannot be used in a script", first)))?;
+                if self.options.dry_run {
+                    self.render(line, text, &cmd, true).await?;
+                } else {
+                    send(self.target, &cmd, self.options.json).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// `fade <fader> <level> [from <level>] over <duration>`
+    async fn fade(&mut self, words: &[String], line: usize, text: &str) -> Result<(), CtlError> {
+        let usage = || CtlError::Invalid("Usage: fade <fader> <level> [from <level>] over <duration>".to_string());
+        let (fader, end, rest) = match words {
+            [_, fader, level, rest @ ..] => (parse_fader(fader)?, parse_level(level)?, rest),
+            _ => return Err(usage()),
+        };
+        let (from, duration) = match rest {
+            [from, start, over, duration] if from == "from" && over == "over" => (Some(parse_level(start)?), duration),
+            [over, duration] if over == "over" => (None, duration),
+            _ => return Err(usage()),
+        };
+        let duration = parse_duration(duration)?;
+
+        let start = match from {
+            Some(start) => start,
+            None => {
+                let faders: Vec<FaderStatus> = serde_json::from_str(&query(self.target, &Command::Faders).await?)?;
+                faders.iter().find(|f| f.fader == fader).and_then(|f| f.level)
+                    .ok_or_else(|| CtlError::Invalid(format!("Level of {} not seen yet - use `fade ... from <level> over ...`", fader)))?
+            }
+        };
+
+        let fade_step = self.options.fade_step;
+        let steps = (duration.as_millis() / fade_step.as_millis()).max(1) as i64;
+        let mut ticker = tokio::time::interval(fade_step);
+        for i in 1..=steps {
+            let level = start as i64 + (end as i64 - start as i64) * i / steps;
+            let cmd = Command::Fader { fader, muted: None, source: None, level: Some(level as f32 / LEVEL_MAX as f32) };
+            if self.options.dry_run {
+                self.render(line, text, &cmd, i == 1).await?;
+            } else {
+                ticker.tick().await;
+                submit(self.target, &cmd).await?;
+            }
+        }
+        if !self.options.dry_run && !self.options.json {
+            println!("Faded {} from {} to {} in {} steps", fader, start, end, steps);
+        }
+        Ok(())
+    }
+
+    /// Ask the bridge for the frames without sending them
+    async fn render(&mut self, line: usize, statement: &str, cmd: &Command, header: bool) -> Result<(), CtlError> {
+        let reply = render(self.target, cmd).await?;
+        if !reply.session_known && !self.session_noted {
+            self.session_noted = true;
+            eprintln!("Note: no session sniffed yet; frames use session {}", reply.session_id);
+        }
+
+        if self.options.json {
+            let output = DryRunOutput { line, statement, command: cmd, frames: &reply.frames, warnings: &reply.warnings };
+            println!("{}", serde_json::to_string_pretty(&output)?);
+            return Ok(());
+        }
+        if header {
+            println!("[line {}] {}", line, statement);
+        }
+        for frame in &reply.frames {
+            println!("  {}", frame);
+        }
+        print_warnings(&reply.warnings);
+        Ok(())
+    }
+}
+
+/// `label name` or `name:`
+fn label_name(text: &str) -> Option<&str> {
+    let text = text.trim();
+    if let Some(name) = text.strip_prefix("label ") {
+        return Some(name.trim());
+    }
+    text.strip_suffix(':').filter(|name| !name.is_empty() && !name.contains(char::is_whitespace))
+}
+
+/// Split a line at `;` and drop `#` comments, both outside quotes
+fn split_statements(line: &str, number: usize) -> Result<Vec<String>, CtlError> {
+    let mut statements = Vec::new();
+    let mut current = String::new();
+    let mut quote = None;
+    for c in line.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (None, '\'' | '"') => quote = Some(c),
+            (None, '#') => break,
+            (None, ';') => {
+                statements.push(std::mem::take(&mut current));
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+    if quote.is_some() {
+        return Err(CtlError::Invalid(format!("Line {}: unterminated quote: {}", number, line)));
+    }
+    statements.push(current);
+    Ok(statements.into_iter().map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+}
+
+/// Split a statement into words; quotes group words and are removed
+fn split_words(text: &str) -> Result<Vec<String>, CtlError> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_word = false;
+    let mut quote = None;
+    for c in text.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_word = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut current));
+                    in_word = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_word = true;
+            }
+        }
+    }
+    if quote.is_some() {
+        return Err(CtlError::Invalid(format!("Unterminated quote: {}", text)));
+    }
+    if in_word {
+        words.push(current);
+    }
+    Ok(words)
+}
+
+/// `500ms`, `2s`, `1.5s`, `1m`
+fn parse_duration(s: &str) -> Result<Duration, CtlError> {
+    let invalid = || CtlError::Invalid(format!("Invalid duration: {} (e.g. 500ms, 2s, 1m)", s));
+    let (number, scale) = if let Some(n) = s.strip_suffix("ms") {
+        (n, 0.001)
+    } else if let Some(n) = s.strip_suffix('s') {
+        (n, 1.0)
+    } else if let Some(n) = s.strip_suffix('m') {
+        (n, 60.0)
+    } else {
+        return Err(invalid());
+    };
+    let value: f64 = number.parse().map_err(|_| invalid())?;
+    Duration::try_from_secs_f64(value * scale).map_err(|_| invalid())
+}
+
+/// Raw level (0-65535) or percentage (`50%`)
+fn parse_level(s: &str) -> Result<u32, CtlError> {
+    let invalid = || CtlError::Invalid(format!("Invalid level: {} (0-{} or 0-100%)", s, LEVEL_MAX));
+    match s.strip_suffix('%') {
+        Some(percent) => {
+            let percent: f64 = percent.parse().map_err(|_| invalid())?;
+            if !(0.0..=100.0).contains(&percent) {
+                return Err(invalid());
+            }
+            Ok((percent / 100.0 * LEVEL_MAX as f64).round() as u32)
+        }
+        None => s.parse().ok().filter(|&l| l <= LEVEL_MAX).ok_or_else(invalid),
+    }
+}
+
+fn yaml_scalar(value: &serde_yaml::Value) -> Result<String, CtlError> {
+    match value {
+        serde_yaml::Value::String(s) => Ok(s.clone()),
+        serde_yaml::Value::Number(n) => Ok(n.to_string()),
+        serde_yaml::Value::Bool(b) => Ok(b.to_string()),
+        _ => Err(CtlError::Invalid(format!("Variables must be strings or numbers, got {:?}", value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(content: &str, file: &str, cli_vars: &[&str]) -> Result<Script, String> {
+        let cli_vars: Vec<String> = cli_vars.iter().map(|v| v.to_string()).collect();
+        Script::parse(content, Path::new(file), &cli_vars).map_err(|e| match e {
+            CtlError::Invalid(message) => message,
+            e => panic!("unexpected error {}", e),
+        })
+    }
+
+    /// Statements the runner would execute, in order, with variables expanded
+    fn walk(script: &mut Script) -> Vec<String> {
+        let mut executed = Vec::new();
+        let mut pc = 0;
+        while pc < script.statements.len() {
+            assert!(executed.len() < 100, "script does not end");
+            match script.step(pc, false).unwrap() {
+                (Flow::Jump(target), _) => {
+                    pc = target;
+                    continue;
+                }
+                (Flow::Next, _) => {}
+                (Flow::Run, text) => executed.push(text),
+            }
+            pc += 1;
+        }
+        executed
+    }
+
+    #[test]
+    fn variables_and_cli_overrides() {
+        let text = "set bus hp1\nset src pad\nmix link $bus ${src}\nset bus hp2; mix unlink $bus $src # $undefined\necho $$bus";
+        let mut script = parse(text, "show.txt", &[]).unwrap();
+        assert_eq!(walk(&mut script), ["mix link hp1 pad", "mix unlink hp2 pad", "echo $bus"]);
+
+        // --set wins over every `set` in the file
+        let mut script = parse(text, "show.txt", &["bus=speaker"]).unwrap();
+        assert_eq!(walk(&mut script), ["mix link speaker pad", "mix unlink speaker pad", "echo $bus"]);
+
+        let mut script = parse("mute $nope", "show.txt", &[]).unwrap();
+        assert!(matches!(script.step(0, false), Err(CtlError::Invalid(e)) if e == "Undefined variable: $nope"));
+        assert_eq!(parse("", "show.txt", &["bus"]).err().unwrap(), "Invalid variable: bus (expected NAME=VALUE)");
+    }
+
+    #[test]
+    fn loops_expand() {
+        let text = "\
+            mute p1
+            outer:
+            label inner
+            fade v2 0 over 1s
+            loop inner 1
+            unmute p1
+            loop outer 1
+            wait 1s";
+        let mut script = parse(text, "show.txt", &[]).unwrap();
+        assert_eq!(walk(&mut script), [
+            "mute p1",
+            "fade v2 0 over 1s", "fade v2 0 over 1s", "unmute p1",
+            // The inner loop counts again when the outer one repeats it
+            "fade v2 0 over 1s", "fade v2 0 over 1s", "unmute p1",
+            "wait 1s",
+        ]);
+
+        // Without a count the jump always happens
+        let mut script = parse("top:\nmute p1\nloop top", "show.txt", &[]).unwrap();
+        assert_eq!(script.step(2, false).unwrap().0, Flow::Jump(0));
+        assert_eq!(script.step(2, false).unwrap().0, Flow::Jump(0));
+        // A dry run shows it once
+        assert_eq!(script.step(2, true).unwrap().0, Flow::Next);
+    }
+
+    #[test]
+    fn bad_lines_name_the_line() {
+        let cases = [
+            ("mute p1\n\nloop nowhere 2", "Line 3: unknown label nowhere"),
+            ("a:\nmute p1\nlabel a", "Line 3: duplicate label a"),
+            ("mute p1\nloop", "Line 2: usage: loop <label> [count]"),
+            ("# intro\nmix link hp1 'pad", "Line 2: unterminated quote: mix link hp1 'pad"),
+        ];
+        for (text, error) in cases {
+            assert_eq!(parse(text, "show.txt", &[]).err().as_deref(), Some(error), "{}", text);
+        }
+        // YAML steps are numbered from 1
+        assert_eq!(
+            parse("steps: ['mute p1', 'loop x']", "show.yaml", &[]).err().as_deref(),
+            Some("Line 2: unknown label x")
+        );
+        assert!(parse("steps: mute", "show.yaml", &[]).err().unwrap().starts_with("Invalid script show.yaml"));
+    }
+
+    #[test]
+    fn yaml_and_text_are_equivalent() {
+        let text = "\
+            set level 50%
+            intro:
+            mute p1; wait 500ms
+            fade v2 $level over 2s   # halfway
+            loop intro 1
+            mix link $bus pad";
+        let yaml = "\
+vars:
+  bus: hp1
+  level: 10%
+steps:
+  - set level 50%
+  - 'intro:'
+  - mute p1; wait 500ms
+  - fade v2 $level over 2s   # halfway
+  - loop intro 1
+  - mix link $bus pad
+";
+        let mut from_text = parse(text, "show.txt", &["bus=hp1"]).unwrap();
+        let mut from_yaml = parse(yaml, "show.yml", &[]).unwrap();
+        let statements = |s: &Script| s.statements.iter().map(|s| (s.line, s.text.clone())).collect::<Vec<_>>();
+        assert_eq!(statements(&from_text), statements(&from_yaml));
+        assert_eq!(from_text.labels, from_yaml.labels);
+
+        let executed = walk(&mut from_text);
+        assert_eq!(executed, walk(&mut from_yaml));
+        assert_eq!(executed, [
+            "mute p1", "wait 500ms", "fade v2 50% over 2s",
+            "mute p1", "wait 500ms", "fade v2 50% over 2s",
+            "mix link hp1 pad",
+        ]);
+    }
+}
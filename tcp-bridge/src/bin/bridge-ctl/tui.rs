@@ -0,0 +1,335 @@
+//! Full-screen view of faders and routing, driven over the control socket.
+//!
+//! Polls `Command::Faders` and `Command::Matrix`; keys send the same
+//! commands as the regular subcommands, so it needs nothing but the socket
+//! (or an api-server with `--remote`).
+
+use std::io;
+use std::time::{Duration, Instant};
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Cell, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+
+use tcp_bridge::commands::{Command, CommandReply, MixAction};
+use tcp_bridge::faders::{FaderStatus, LEVEL_MAX};
+use tcp_bridge::names::{Fader, MixOutput, Source};
+use tcp_bridge::routing::{MatrixSnapshot, MixState};
+
+use crate::error::CtlError;
+use crate::{Target, query};
+
+const REFRESH: Duration = Duration::from_millis(500);
+/// Level change per key press (5%)
+const LEVEL_STEP: u32 = LEVEL_MAX / 20;
+const BAR_WIDTH: usize = 20;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Focus {
+    Faders,
+    Matrix,
+}
+
+struct App {
+    target: Target,
+    faders: Vec<FaderStatus>,
+    matrix: MatrixSnapshot,
+    focus: Focus,
+    fader: usize,
+    /// Matrix cursor: row = source, column = mix
+    row: usize,
+    col: usize,
+    /// Last result line and whether it is an error
+    message: Option<(String, bool)>,
+    connected: bool,
+}
+
+pub async fn run(target: &Target) -> Result<(), CtlError> {
+    let mut app = App {
+        target: target.clone(),
+        faders: Vec::new(),
+        matrix: MatrixSnapshot::default(),
+        focus: Focus::Faders,
+        fader: 0,
+        row: 0,
+        col: 0,
+        message: None,
+        connected: false,
+    };
+    // Fail before taking over the terminal if the bridge is not there
+    (app.faders, app.matrix) = app.fetch().await?;
+    app.connected = true;
+
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal).await;
+    ratatui::restore();
+    Ok(result?)
+}
+
+impl App {
+    async fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        let mut last_refresh = Instant::now();
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+
+            let timeout = REFRESH.saturating_sub(last_refresh.elapsed());
+            if event::poll(timeout)?
+                && let Event::Key(key) = event::read()?
+                && key.kind == KeyEventKind::Press
+            {
+                let quit = matches!(key.code, KeyCode::Char('q') | KeyCode::Esc)
+                    || (key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL));
+                if quit {
+                    return Ok(());
+                }
+                if self.handle_key(key.code).await {
+                    // Show the result without waiting for the next poll
+                    last_refresh -= REFRESH;
+                }
+            }
+
+            if last_refresh.elapsed() >= REFRESH {
+                self.refresh().await;
+                last_refresh = Instant::now();
+            }
+        }
+    }
+
+    async fn fetch(&self) -> Result<(Vec<FaderStatus>, MatrixSnapshot), CtlError> {
+        let faders = serde_json::from_str(&query(&self.target, &Command::Faders).await?)?;
+        let matrix = serde_json::from_str(&query(&self.target, &Command::Matrix).await?)?;
+        Ok((faders, matrix))
+    }
+
+    async fn refresh(&mut self) {
+        match self.fetch().await {
+            Ok((faders, matrix)) => {
+                self.faders = faders;
+                self.matrix = matrix;
+                if !self.connected {
+                    self.connected = true;
+                    self.message = None;
+                }
+            }
+            Err(e) => {
+                self.connected = false;
+                self.message = Some((format!("Bridge not reachable: {}", e), true));
+            }
+        }
+    }
+
+    /// Returns true if a command was sent
+    async fn handle_key(&mut self, key: KeyCode) -> bool {
+        if key == KeyCode::Tab || key == KeyCode::BackTab {
+            self.focus = match self.focus {
+                Focus::Faders => Focus::Matrix,
+                Focus::Matrix => Focus::Faders,
+            };
+            return false;
+        }
+        let cmd = match self.focus {
+            Focus::Faders => self.fader_key(key),
+            Focus::Matrix => self.matrix_key(key),
+        };
+        match cmd {
+            Some(cmd) => {
+                self.send(cmd).await;
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn fader_key(&mut self, key: KeyCode) -> Option<Command> {
+        let fader = Fader::ALL[self.fader];
+        let status = self.faders.get(self.fader);
+        let change_level = |up: bool| {
+            let level = status?.level?;
+            let level = if up { (level + LEVEL_STEP).min(LEVEL_MAX) } else { level.saturating_sub(LEVEL_STEP) };
+            Some(Command::Fader { fader, muted: None, source: None, level: Some(level as f32 / LEVEL_MAX as f32) })
+        };
+        match key {
+            KeyCode::Up | KeyCode::Char('k') => self.fader = self.fader.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') => self.fader = (self.fader + 1).min(Fader::ALL.len() - 1),
+            KeyCode::Char('m') | KeyCode::Char(' ') => {
+                let muted = status.and_then(|s| s.muted).unwrap_or(false);
+                return Some(Command::Fader { fader, muted: Some(!muted), source: None, level: None });
+            }
+            KeyCode::Right | KeyCode::Char('+') | KeyCode::Char('=') | KeyCode::Left | KeyCode::Char('-') => {
+                let up = matches!(key, KeyCode::Right | KeyCode::Char('+') | KeyCode::Char('='));
+                let cmd = change_level(up);
+                if cmd.is_none() {
+                    self.message = Some((format!("Level of {} not seen yet; move it on the device first", fader), true));
+                }
+                return cmd;
+            }
+            _ => {}
+        }
+        None
+    }
+
+    fn matrix_key(&mut self, key: KeyCode) -> Option<Command> {
+        let mix = MixOutput::ALL[self.col];
+        let source = Source::ALL[self.row];
+        let action = match key {
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.row = self.row.saturating_sub(1);
+                return None;
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.row = (self.row + 1).min(Source::ALL.len() - 1);
+                return None;
+            }
+            KeyCode::Left => {
+                self.col = self.col.saturating_sub(1);
+                return None;
+            }
+            KeyCode::Right => {
+                self.col = (self.col + 1).min(MixOutput::ALL.len() - 1);
+                return None;
+            }
+            KeyCode::Char('l') => MixAction::Link,
+            KeyCode::Char('u') => MixAction::Unlink,
+            KeyCode::Char('d') => MixAction::Disable,
+            KeyCode::Char('e') => MixAction::Enable,
+            // Toggle between routed and not routed
+            KeyCode::Char(' ') => match self.matrix.get(mix, source) {
+                Some(MixState::Linked) | Some(MixState::Unlinked) => MixAction::Disable,
+                Some(MixState::Disabled) | None => MixAction::Link,
+            },
+            _ => return None,
+        };
+        Some(Command::Mix { action, mix, source })
+    }
+
+    async fn send(&mut self, cmd: Command) {
+        if let Err(e) = cmd.validate() {
+            self.message = Some((format!("{} [{}]", e, e.code()), true));
+            return;
+        }
+        let reply = async {
+            let reply: CommandReply = serde_json::from_str(&query(&self.target, &cmd).await?)?;
+            Ok::<_, CtlError>(reply)
+        }.await;
+
+        self.message = Some(match reply {
+            Ok(CommandReply { error: Some(e), .. }) => (format!("{} [{}]", e, e.code()), true),
+            Ok(reply) => match reply.warnings.first() {
+                Some(w) => (format!("Warning: {} [{}]", w, w.code()), false),
+                None => (format!("Sent: {}", describe(&cmd)), false),
+            },
+            Err(e) => (format!("Send failed: {}", e), true),
+        });
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [faders, matrix, footer] = Layout::vertical([
+            Constraint::Length(Fader::ALL.len() as u16 + 3),
+            Constraint::Min(0),
+            Constraint::Length(2),
+        ]).areas(frame.area());
+
+        frame.render_widget(self.fader_table(), faders);
+        frame.render_widget(self.matrix_table(), matrix);
+        frame.render_widget(self.footer(), footer);
+    }
+
+    fn fader_table(&self) -> Table<'_> {
+        let rows = Fader::ALL.iter().enumerate().map(|(i, fader)| {
+            let status = self.faders.get(i);
+            let source = match status {
+                Some(FaderStatus { source: Some(s), .. }) => s.to_string(),
+                Some(FaderStatus { source_id: Some(id), .. }) => format!("#{}", id),
+                _ => "?".to_string(),
+            };
+            let (mute, mute_style) = match status.and_then(|s| s.muted) {
+                Some(true) => ("MUTED", Style::new().fg(Color::Red).add_modifier(Modifier::BOLD)),
+                Some(false) => ("on", Style::new().fg(Color::Green)),
+                None => ("?", Style::new()),
+            };
+            let level = match status.and_then(|s| s.level) {
+                Some(level) => {
+                    let filled = (level as usize * BAR_WIDTH).div_ceil(LEVEL_MAX as usize);
+                    format!("{:>3}% {}{}", level * 100 / LEVEL_MAX, "█".repeat(filled), "·".repeat(BAR_WIDTH - filled))
+                }
+                None => "   ?".to_string(),
+            };
+            let row = Row::new(vec![
+                Cell::from(fader.short_name()),
+                Cell::from(source),
+                Cell::from(mute).style(mute_style),
+                Cell::from(level),
+            ]);
+            if self.focus == Focus::Faders && i == self.fader {
+                row.style(Style::new().add_modifier(Modifier::REVERSED))
+            } else {
+                row
+            }
+        });
+
+        let header = Row::new(["Fader", "Source", "Mute", "Level"]).style(Style::new().add_modifier(Modifier::BOLD));
+        Table::new(rows, [Constraint::Length(6), Constraint::Length(12), Constraint::Length(6), Constraint::Min(10)])
+            .header(header)
+            .block(self.block("Faders", Focus::Faders))
+    }
+
+    fn matrix_table(&self) -> Table<'_> {
+        let rows = Source::ALL.iter().enumerate().map(|(r, &source)| {
+            let mut cells = vec![Cell::from(source.to_string())];
+            cells.extend(MixOutput::ALL.iter().enumerate().map(|(c, &mix)| {
+                let (marker, color) = match self.matrix.get(mix, source) {
+                    Some(MixState::Linked) => ("L", Color::Green),
+                    Some(MixState::Unlinked) => ("U", Color::Yellow),
+                    Some(MixState::Disabled) => ("-", Color::DarkGray),
+                    None => ("?", Color::Reset),
+                };
+                let mut style = Style::new().fg(color);
+                if self.focus == Focus::Matrix && (r, c) == (self.row, self.col) {
+                    style = style.add_modifier(Modifier::REVERSED);
+                }
+                Cell::from(format!("{:>4}", marker)).style(style)
+            }));
+            Row::new(cells)
+        });
+
+        let header = std::iter::once(String::new())
+            .chain(MixOutput::ALL.iter().map(|m| format!("{:>4}", m.short_name())));
+        let widths = std::iter::once(Constraint::Length(10))
+            .chain(MixOutput::ALL.iter().map(|_| Constraint::Length(4)));
+        Table::new(rows, widths)
+            .header(Row::new(header).style(Style::new().add_modifier(Modifier::BOLD)))
+            .block(self.block("Routing (L = linked, U = unlinked, - = disabled, ? = not seen yet)", Focus::Matrix))
+    }
+
+    fn block(&self, title: &str, focus: Focus) -> Block<'static> {
+        let style = if self.focus == focus { Style::new().fg(Color::Cyan) } else { Style::new() };
+        Block::bordered().title(format!(" {} ", title)).border_style(style)
+    }
+
+    fn footer(&self) -> Paragraph<'_> {
+        let keys = match self.focus {
+            Focus::Faders => "↑↓ select  m/space mute  ←→ or -/+ level  tab routing  q quit",
+            Focus::Matrix => "arrows move  space toggle  l link  u unlink  d disable  e enable  tab faders  q quit",
+        };
+        let message = match &self.message {
+            Some((m, true)) => Line::styled(m.as_str(), Style::new().fg(Color::Red)),
+            Some((m, false)) => Line::raw(m.as_str()),
+            None => Line::raw(""),
+        };
+        Paragraph::new(vec![message, Line::styled(keys, Style::new().fg(Color::DarkGray))])
+    }
+}
+
+fn describe(cmd: &Command) -> String {
+    match cmd {
+        Command::Mix { action, mix, source } => format!("{:?} {} in {}", action, source, mix),
+        Command::Fader { fader, muted: Some(muted), .. } => {
+            format!("{} {}", if *muted { "mute" } else { "unmute" }, fader)
+        }
+        Command::Fader { fader, level: Some(level), .. } => format!("{} level {:.0}%", fader, level * 100.0),
+        other => format!("{:?}", other),
+    }
+}
@@ -0,0 +1,266 @@
+//! `encode` / `decode`: frames on the wire without a bridge or mixer.
+//!
+//! `encode` converts a subcommand exactly like the bridge does (see
+//! `tcp_bridge::inject`), using a given or placeholder session ID.
+//! Routing requests are planned against an empty matrix, so every cell
+//! counts as unknown. `decode` splits frames into their fields:
+//!
+//! ```text
+//! bridge-ctl encode mute p1 | bridge-ctl decode
+//! ```
+
+use serde::Serialize;
+use std::io::Read;
+use std::path::Path;
+
+use tcp_bridge::commands::{Command, RenderReply};
+use tcp_bridge::faders::LEVEL_MAX;
+use tcp_bridge::inject;
+use tcp_bridge::names::Fader;
+use tcp_bridge::protocol::{self, CallMeChannel, CommandSpecs, Message, MixRequestFlags, Packet, RodeDecode, WireValue};
+use tcp_bridge::routing::{self, RoutingMatrix};
+
+use crate::error::CtlError;
+use crate::print_warnings;
+
+/// One frame split into its fields
+#[derive(Debug, Serialize)]
+struct DecodedFrame {
+    /// Byte offset of the frame in the input
+    offset: usize,
+    /// Header was the `2c9eb4f2` magic (false: input was a bare payload)
+    framed: bool,
+    length: usize,
+    payload: String,
+    ping: bool,
+    session: Option<String>,
+    prefix: Option<String>,
+    property: Option<String>,
+    value_type: Option<u8>,
+    value: Option<String>,
+    /// Value interpreted by its type (bool, integer, link request flags)
+    decoded: Option<String>,
+    /// What the frame does, for properties the bridge knows
+    meaning: Option<String>,
+}
+
+/// Frames for a command, as the bridge would inject them
+pub fn encode(
+    cmd: &Command,
+    session: Option<&str>,
+    callme_session: Option<&str>,
+    spec: &Path,
+) -> Result<RenderReply, CtlError> {
+    cmd.validate()?;
+    let session_id = match session {
+        Some(s) => parse_session(s)?,
+        None => protocol::DEFAULT_SESSION_ID.to_vec(),
+    };
+    let callme_session = callme_session.map(parse_session).transpose()?;
+    // Built-in commands work without specs, like in the bridge
+    let specs = CommandSpecs::load(spec).unwrap_or_default();
+
+    let prepared = inject::prepare(cmd, &specs, &RoutingMatrix::default())?;
+    if callme_session.is_none() && prepared.commands.iter().any(inject::ProxyCommand::needs_callme_session) {
+        return Err(CtlError::Invalid("CallMe routing needs --callme-session <hex>".to_string()));
+    }
+    // Offline, the CallMe prefixes follow the known layout
+    let callme = callme_session.map(|session| CallMeChannel { session, prefix_base: protocol::CALLME_PREFIX_BASE });
+    let frames = inject::frames(&prepared.commands, &session_id, callme.as_ref())
+        .map_err(|e| CtlError::Invalid(e.to_string()))?;
+    Ok(RenderReply {
+        frames: frames.iter().map(hex::encode).collect(),
+        session_id: hex::encode(&session_id),
+        session_known: session.is_some(),
+        warnings: prepared.warnings,
+        error: None,
+    })
+}
+
+/// `encode` output: one hex frame per line, so it can be piped into `decode`
+pub fn print_encoded(reply: &RenderReply, json: bool) -> Result<(), CtlError> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(reply)?);
+        return Ok(());
+    }
+    if !reply.session_known {
+        eprintln!("Note: placeholder session {}; use --session <hex> for a real one", reply.session_id);
+    }
+    if reply.frames.is_empty() {
+        eprintln!("Nothing to send");
+    }
+    for frame in &reply.frames {
+        println!("{}", frame);
+    }
+    print_warnings(&reply.warnings);
+    Ok(())
+}
+
+/// Decode hex frames from the arguments, or from stdin if there are none
+pub fn decode(input: &[String], json: bool) -> Result<(), CtlError> {
+    let text = if input.is_empty() {
+        let mut text = String::new();
+        std::io::stdin().read_to_string(&mut text)?;
+        text
+    } else {
+        input.join(" ")
+    };
+    let hex: String = text.chars()
+        .filter(|c| !c.is_whitespace() && *c != ':')
+        .collect::<String>()
+        .trim_start_matches("0x")
+        .to_string();
+    let bytes = hex::decode(&hex).map_err(|e| CtlError::Invalid(format!("Invalid hex: {}", e)))?;
+    if bytes.is_empty() {
+        return Err(CtlError::Invalid("Nothing to decode".to_string()));
+    }
+
+    let frames = split_frames(&bytes)?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&frames)?);
+        return Ok(());
+    }
+    for (i, frame) in frames.iter().enumerate() {
+        if i > 0 {
+            println!();
+        }
+        print_frame(i + 1, frame);
+    }
+    Ok(())
+}
+
+/// Split input into frames; input without the magic is one bare payload
+fn split_frames(bytes: &[u8]) -> Result<Vec<DecodedFrame>, CtlError> {
+    let magic = Packet::MAGIC.to_le_bytes();
+    if !bytes.starts_with(&magic) {
+        return Ok(vec![decode_payload(0, false, bytes)]);
+    }
+
+    let mut frames = Vec::new();
+    let mut offset = 0;
+    while offset < bytes.len() {
+        let rest = &bytes[offset..];
+        if rest.len() < 8 || rest[..4] != magic {
+            return Err(CtlError::Invalid(format!("No frame header at byte {}", offset)));
+        }
+        let length = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        let payload = rest.get(8..8 + length).ok_or_else(|| CtlError::Invalid(format!(
+            "Frame at byte {} declares {} payload bytes, only {} given", offset, length, rest.len() - 8
+        )))?;
+        frames.push(decode_payload(offset, true, payload));
+        offset += 8 + length;
+    }
+    Ok(frames)
+}
+
+fn decode_payload(offset: usize, framed: bool, payload: &[u8]) -> DecodedFrame {
+    let mut frame = DecodedFrame {
+        offset,
+        framed,
+        length: payload.len(),
+        payload: hex::encode(payload),
+        ping: protocol::is_ping(payload),
+        session: None,
+        prefix: None,
+        property: None,
+        value_type: None,
+        value: None,
+        decoded: None,
+        meaning: None,
+    };
+    if frame.ping {
+        frame.meaning = Some(String::from_utf8_lossy(&payload[..4]).into_owned());
+        return frame;
+    }
+    let Some(msg) = Message::parse(payload) else { return frame };
+
+    frame.session = Some(hex::encode(msg.session));
+    frame.prefix = Some(hex::encode(msg.prefix));
+    frame.property = Some(msg.property.to_string());
+    // Values start with 0x01 and the type byte
+    if let [0x01, ty, data @ ..] = msg.value {
+        frame.value_type = Some(*ty);
+        frame.value = Some(hex::encode(data));
+        frame.decoded = match ty {
+            0x01 => bool::decode(data).map(|b| b.to_string()),
+            0x05 => u32::decode(data).map(|v| v.to_string()),
+            0x07 => MixRequestFlags::decode(data).map(|f| format!("{:?}", f.0)),
+            _ => None,
+        };
+    }
+    frame.meaning = meaning(payload, &msg);
+    frame
+}
+
+/// Describe frames of the properties the bridge sends or tracks
+fn meaning(payload: &[u8], msg: &Message) -> Option<String> {
+    let fader = |index: u8| Fader::from_index(index).map_or(format!("fader #{}", index), |f| f.to_string());
+    let state = |flag: bool, yes: &str, no: &str| if flag { yes.to_string() } else { no.to_string() };
+    let value_bool = || match msg.value {
+        [0x01, 0x01, data @ ..] => bool::decode(data),
+        _ => None,
+    };
+
+    Some(match msg.property {
+        "channelOutputMute" => {
+            let m = protocol::ChannelOutputMute::decode_payload(payload)?;
+            format!("{} {}", fader(m.fader_index), state(m.mute, "muted", "unmuted"))
+        }
+        "channelInputSource" => {
+            let s = protocol::ChannelInputSource::decode_payload(payload)?;
+            format!("{} source ID {}", fader(s.fader_index), s.source_id)
+        }
+        "inputMicrophoneType" => {
+            let m = protocol::InputMicrophoneType::decode_payload(payload)?;
+            format!("{} mic type {}", fader(m.fader_index), m.mic_type as i32)
+        }
+        "faderLevel" => {
+            let l = protocol::FaderLevel::decode_payload(payload)?;
+            format!("{} level {} ({}%)", fader(l.fader_index), l.level, l.level as u64 * 100 / LEVEL_MAX as u64)
+        }
+        "screenTouched" => "screen touched".to_string(),
+        "mixDisabled" | "mixLink" | "mixMute" | "mixLinkRequest" | "mixUnlinkRequest" => {
+            let (mix, source) = routing::cell_for_prefix(msg.prefix, Some(protocol::CALLME_PREFIX_BASE))?;
+            let what = match msg.property {
+                "mixDisabled" => state(value_bool()?, "disabled", "enabled"),
+                "mixLink" => state(value_bool()?, "linked", "unlinked"),
+                "mixMute" => state(value_bool()?, "muted", "unmuted"),
+                "mixLinkRequest" => "link request".to_string(),
+                _ => "unlink request".to_string(),
+            };
+            format!("{} <- {}: {}", mix, source, what)
+        }
+        _ => return None,
+    })
+}
+
+fn print_frame(n: usize, frame: &DecodedFrame) {
+    let kind = if frame.framed { "frame" } else { "payload" };
+    println!("Frame {} ({} at byte {}, {} payload bytes)", n, kind, frame.offset, frame.length);
+    if frame.ping {
+        println!("  ping      {}", frame.meaning.as_deref().unwrap_or_default());
+        return;
+    }
+    let Some(property) = &frame.property else {
+        println!("  payload   {} (not a property frame)", frame.payload);
+        return;
+    };
+    println!("  session   {}", frame.session.as_deref().unwrap_or_default());
+    println!("  prefix    {}", frame.prefix.as_deref().unwrap_or_default());
+    println!("  property  {}", property);
+    match (frame.value_type, &frame.value) {
+        (Some(ty), Some(value)) => {
+            let decoded = frame.decoded.as_ref().map_or(String::new(), |d| format!(" = {}", d));
+            println!("  value     type 0x{:02x}: {}{}", ty, value, decoded);
+        }
+        _ => println!("  value     (none)"),
+    }
+    if let Some(meaning) = &frame.meaning {
+        println!("  meaning   {}", meaning);
+    }
+}
+
+fn parse_session(s: &str) -> Result<Vec<u8>, CtlError> {
+    let invalid = || CtlError::Invalid(format!("Invalid session ID: {} (expected {} hex bytes)", s, protocol::SESSION_ID_LEN));
+    hex::decode(s).ok().filter(|b| b.len() == protocol::SESSION_ID_LEN).ok_or_else(invalid)
+}
@@ -2,7 +2,10 @@
 //! Uses JSON serialization for type-safe communication.
 
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
 use crate::names::{MixOutput, Source, Fader};
+use crate::routing::{self, MixState, RoutingMatrix};
 
 /// Actions for mix commands
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
@@ -11,6 +14,7 @@ pub enum MixAction {
     Link,    // Ensures routing is active (sends enable + link)
     Unlink,  // Sets to unlinked/fixed level
     Disable, // Mutes the routing
+    Enable,  // Restores routing, keeping link mode and fixed level
 }
 
 /// Unified command enum - serialized as JSON for IPC
@@ -23,6 +27,27 @@ pub enum Command {
         mix: MixOutput,
         source: Source,
     },
+    /// Declare the routing of a whole bus, e.g. `{"combo2": "linked", "*": "disabled"}`.
+    /// Keys are source names or `*` for every source not listed.
+    /// Replied to with a `routing::RoutingReply` listing the changed cells.
+    Bus {
+        mix: MixOutput,
+        routes: BTreeMap<String, MixState>,
+    },
+    /// Make `to` route exactly like `from` (replied to like `Bus`)
+    MixCopy {
+        from: MixOutput,
+        to: MixOutput,
+    },
+    /// Exchange the routing of two buses (replied to like `Bus`)
+    MixSwap {
+        first: MixOutput,
+        second: MixOutput,
+    },
+    /// Build a mix-minus on each remote's return bus (replied to like `Bus`)
+    MixMinus {
+        remotes: Vec<Source>,
+    },
     /// Fader control command
     Fader {
         fader: Fader,
@@ -33,16 +58,322 @@ pub enum Command {
         #[serde(default, skip_serializing_if = "Option::is_none")]
         level: Option<f32>,
     },
+    /// Raw `channelInputSource` ID plus the `inputMicrophoneType` -1, 4 sequence
+    /// that keeps the GUI consistent; used to probe IDs missing from `Source`
+    RawSource {
+        fader: Fader,
+        source_id: u32,
+    },
     /// Screen touch event
     Touch,
+    /// Query bridge status (replied to on the control socket)
+    Status,
+    /// Query the routing matrix (replied to with a `routing::MatrixSnapshot`)
+    Matrix,
+    /// Query fader mute/source/level (replied to with `Vec<faders::FaderStatus>`)
+    Faders,
+    /// Validate `command` and build its frames without sending anything
+    /// (replied to with a `RenderReply`)
+    Render {
+        command: Box<Command>,
+    },
+    /// Query recent frames (replied to with a `traffic::TrafficSnapshot`)
+    Traffic {
+        /// Only frames with this sequence number or later
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        since: Option<u64>,
+    },
+    /// Re-read the config and spec file, like SIGHUP (replied to with a `ReloadReply`)
+    Reload,
+    /// Command defined in the spec file loaded by the bridge
+    Spec {
+        name: String,
+        index: u8,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        value: Option<i64>,
+    },
+}
+
+/// Commands rejected before anything reaches the wire
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum CommandError {
+    /// The mixDisabled encoding for CallMe's two-byte prefix is unknown
+    #[serde(rename = "callme_disable_unsupported")]
+    CallMeDisableUnsupported { mix: MixOutput, source: Source },
+    /// Same as above - enable is a mixDisabled write too
+    #[serde(rename = "callme_enable_unsupported")]
+    CallMeEnableUnsupported { mix: MixOutput, source: Source },
+    /// CallMe commands need the CallMe session and prefix layout, learned from traffic
+    #[serde(rename = "callme_session_unknown")]
+    CallMeSessionUnknown,
+    /// No GUI connection through the bridge to inject into
+    NotConnected,
+    /// No session ID sniffed yet (and `--allow-default-session` not set)
+    NoSession,
+    /// Copy or swap with the same bus on both sides
+    SameBus { mix: MixOutput },
+    /// Mix-minus remote without a return bus (only Chat, USB 2 and CallMe have one)
+    NoReturnBus { source: Source },
+    /// Routing a source into the bus that goes back to the same device
+    FeedbackLoop { mix: MixOutput, source: Source },
+    /// Source name in a bus declaration that does not parse
+    UnknownSource { name: String },
+    /// Spec command the bridge could not resolve (unknown name, index, value)
+    InvalidSpec { message: String },
+}
+
+impl CommandError {
+    /// The command was fine but the bridge could not inject it right now
+    pub fn is_session_error(&self) -> bool {
+        matches!(self, Self::NotConnected | Self::NoSession | Self::CallMeSessionUnknown)
+    }
+
+    /// Machine-readable code, same as the `code` field in JSON replies
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::CallMeDisableUnsupported { .. } => "callme_disable_unsupported",
+            Self::CallMeEnableUnsupported { .. } => "callme_enable_unsupported",
+            Self::CallMeSessionUnknown => "callme_session_unknown",
+            Self::NotConnected => "not_connected",
+            Self::NoSession => "no_session",
+            Self::SameBus { .. } => "same_bus",
+            Self::NoReturnBus { .. } => "no_return_bus",
+            Self::FeedbackLoop { .. } => "feedback_loop",
+            Self::UnknownSource { .. } => "unknown_source",
+            Self::InvalidSpec { .. } => "invalid_spec",
+        }
+    }
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CallMeDisableUnsupported { mix, source } => write!(
+                f, "Cannot disable {} in {}: disable is not supported for CallMe sources (use unlink)",
+                source, mix
+            ),
+            Self::CallMeEnableUnsupported { mix, source } => write!(
+                f, "Cannot enable {} in {}: enable is not supported for CallMe sources (use link)",
+                source, mix
+            ),
+            Self::CallMeSessionUnknown => write!(
+                f, "CallMe session not observed yet - change a CallMe route on the device first"
+            ),
+            Self::NotConnected => write!(f, "No GUI connected through the bridge - nothing to inject into"),
+            Self::NoSession => write!(
+                f, "No session ID sniffed yet - wait for the GUI to talk to the mixer"
+            ),
+            Self::SameBus { mix } => write!(f, "Both buses are {}: pick two different buses", mix),
+            Self::NoReturnBus { source } => write!(
+                f, "{} has no return bus (remotes: chat, usb2, callme1-3)", source
+            ),
+            Self::FeedbackLoop { mix, source } => write!(
+                f, "Cannot route {} into {}: it would feed the device its own audio back", source, mix
+            ),
+            Self::UnknownSource { name } => write!(f, "Unknown source: {}", name),
+            Self::InvalidSpec { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+/// Problems that do not stop a command but are reported back
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "code", rename_all = "snake_case")]
+pub enum CommandWarning {
+    /// Source and bus are different channels of the same device (USB 1 main and chat)
+    SharedDeviceEcho { mix: MixOutput, source: Source },
+    /// A source is already routed into the bus that goes back to it
+    FeedbackLoop { mix: MixOutput, source: Source, state: MixState },
+    /// A CallMe source cannot be disabled in its own bus, so a loop cannot be ruled out
+    #[serde(rename = "callme_loop_unchecked")]
+    CallMeLoopUnchecked { mix: MixOutput, source: Source },
+}
+
+impl CommandWarning {
+    /// Machine-readable code, same as the `code` field in JSON replies
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::SharedDeviceEcho { .. } => "shared_device_echo",
+            Self::FeedbackLoop { .. } => "feedback_loop",
+            Self::CallMeLoopUnchecked { .. } => "callme_loop_unchecked",
+        }
+    }
+}
+
+impl fmt::Display for CommandWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::SharedDeviceEcho { mix, source } => write!(
+                f, "{} and {} are the same USB device: the computer will hear itself", source, mix
+            ),
+            Self::FeedbackLoop { mix, source, state } => write!(
+                f, "Feedback loop: {} is {} in its own return bus {}", source, state, mix
+            ),
+            Self::CallMeLoopUnchecked { mix, source } => write!(
+                f, "Possible feedback loop: {} cannot be disabled in {} (CallMe); check it on the device",
+                source, mix
+            ),
+        }
+    }
+}
+
+/// Reply to a non-query command on the control socket
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CommandReply {
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<CommandWarning>,
+    /// Set when the command was rejected; nothing was sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<CommandError>,
+}
+
+/// Reply to `Command::Render`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RenderReply {
+    /// Complete frames (header and payload), hex-encoded, in sending order
+    #[serde(default)]
+    pub frames: Vec<String>,
+    /// Session ID in the frames; the default `01010101` if none was sniffed yet
+    #[serde(default)]
+    pub session_id: String,
+    #[serde(default)]
+    pub session_known: bool,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<CommandWarning>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<CommandError>,
+}
+
+/// Reply to `Command::Reload`; settings are given as `section.key`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReloadReply {
+    /// Changed settings now in effect
+    #[serde(default)]
+    pub applied: Vec<String>,
+    /// Changed settings the bridge keeps until it is restarted
+    #[serde(default)]
+    pub restart_required: Vec<String>,
+    /// Changed settings only `api-server` or `bridge-ctl` read
+    #[serde(default)]
+    pub not_used: Vec<String>,
+    /// Command specs loaded from `proxy.spec`
+    #[serde(default)]
+    pub specs: usize,
+    /// Set when the config or spec file is invalid; nothing was changed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// True if `mix` goes back to the device `source` comes from
+pub fn is_feedback_loop(mix: MixOutput, source: Source) -> bool {
+    matches!(
+        (source, mix),
+        (Source::Usb1, MixOutput::Usb1)
+            | (Source::Chat, MixOutput::Chat)
+            | (Source::Usb2, MixOutput::Usb2)
+            | (Source::Bluetooth, MixOutput::Bluetooth)
+            | (Source::CallMe1, MixOutput::CallMe1)
+            | (Source::CallMe2, MixOutput::CallMe2)
+            | (Source::CallMe3, MixOutput::CallMe3)
+    )
+}
+
+/// Check routing `source` into `mix` in the given state: feedback loops are
+/// rejected, routes between the two USB 1 channels are warned about
+pub fn check_route(mix: MixOutput, source: Source, state: MixState) -> Result<Option<CommandWarning>, CommandError> {
+    if state == MixState::Disabled {
+        return Ok(None);
+    }
+    if is_feedback_loop(mix, source) {
+        return Err(CommandError::FeedbackLoop { mix, source });
+    }
+    // USB 1 carries both the main and the chat channel of one computer
+    match (source, mix) {
+        (Source::Usb1, MixOutput::Chat) | (Source::Chat, MixOutput::Usb1) => {
+            Ok(Some(CommandWarning::SharedDeviceEcho { mix, source }))
+        }
+        _ => Ok(None),
+    }
 }
 
 impl Command {
+    /// Reject commands the protocol cannot express and routes that always
+    /// loop. Needs no bridge state, so clients run it before sending.
+    pub fn validate(&self) -> Result<(), CommandError> {
+        match self {
+            Command::Mix { action: MixAction::Link | MixAction::Enable, mix, source }
+                if is_feedback_loop(*mix, *source) =>
+            {
+                Err(CommandError::FeedbackLoop { mix: *mix, source: *source })
+            }
+            Command::Mix { action: MixAction::Disable, mix, source } if source.is_callme() => {
+                Err(CommandError::CallMeDisableUnsupported { mix: *mix, source: *source })
+            }
+            Command::Mix { action: MixAction::Enable, mix, source } if source.is_callme() => {
+                Err(CommandError::CallMeEnableUnsupported { mix: *mix, source: *source })
+            }
+            Command::MixCopy { from: a, to: b } | Command::MixSwap { first: a, second: b } if a == b => {
+                Err(CommandError::SameBus { mix: *a })
+            }
+            Command::MixMinus { remotes } => {
+                match remotes.iter().find(|src| routing::return_bus(**src).is_none()) {
+                    Some(source) => Err(CommandError::NoReturnBus { source: *source }),
+                    None => Ok(()),
+                }
+            }
+            Command::Bus { mix, routes } => {
+                for (name, state) in routes.iter().filter(|(name, _)| *name != "*") {
+                    let source: Source = name.parse()
+                        .map_err(|_| CommandError::UnknownSource { name: name.clone() })?;
+                    if source.is_callme() && *state == MixState::Disabled {
+                        return Err(CommandError::CallMeDisableUnsupported { mix: *mix, source });
+                    }
+                    check_route(*mix, source, *state)?;
+                }
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Full check against the bridge's routing matrix. Unlink only loops
+    /// if the cell is routed, which only the bridge knows.
+    pub fn check(&self, matrix: &RoutingMatrix) -> Result<Vec<CommandWarning>, CommandError> {
+        self.validate()?;
+        let Command::Mix { action, mix, source } = *self else {
+            return Ok(Vec::new());
+        };
+        let state = match action {
+            MixAction::Link | MixAction::Enable => MixState::Linked,
+            MixAction::Disable => MixState::Disabled,
+            // Unlinking keeps a disabled cell disabled; unknown cells count as routed
+            MixAction::Unlink => match matrix.get(mix, source) {
+                Some(MixState::Disabled) => MixState::Disabled,
+                _ => MixState::Unlinked,
+            },
+        };
+        Ok(check_route(mix, source, state)?.into_iter().collect())
+    }
+
+    /// Routing requests are planned against the routing matrix and answered
+    /// with a `routing::RoutingReply`
+    pub fn is_routing(&self) -> bool {
+        matches!(self, Command::Bus { .. } | Command::MixCopy { .. } | Command::MixSwap { .. } | Command::MixMinus { .. })
+    }
+
     /// Create a mix link command
     pub fn mix_link(mix: MixOutput, source: Source) -> Self {
         Command::Mix { action: MixAction::Link, mix, source }
     }
 
+    /// Create a mix enable command
+    pub fn mix_enable(mix: MixOutput, source: Source) -> Self {
+        Command::Mix { action: MixAction::Enable, mix, source }
+    }
+
     /// Create a mix unlink command
     pub fn mix_unlink(mix: MixOutput, source: Source) -> Self {
         Command::Mix { action: MixAction::Unlink, mix, source }
@@ -58,3 +389,100 @@ impl Command {
         Command::Fader { fader, muted: None, source: None, level: Some(level) }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use MixAction::*;
+    use MixOutput as M;
+    use Source as S;
+
+    fn mix(action: MixAction, mix: MixOutput, source: Source) -> Command {
+        Command::Mix { action, mix, source }
+    }
+
+    fn bus(mix: MixOutput, routes: &[(&str, MixState)]) -> Command {
+        Command::Bus { mix, routes: routes.iter().map(|(name, state)| (name.to_string(), *state)).collect() }
+    }
+
+    #[test]
+    fn feedback_loop_pairs() {
+        let loops = [
+            (M::Usb1, S::Usb1), (M::Chat, S::Chat), (M::Usb2, S::Usb2), (M::Bluetooth, S::Bluetooth),
+            (M::CallMe1, S::CallMe1), (M::CallMe2, S::CallMe2), (M::CallMe3, S::CallMe3),
+        ];
+        for m in MixOutput::ALL {
+            for s in Source::ALL {
+                assert_eq!(is_feedback_loop(m, s), loops.contains(&(m, s)), "{} -> {}", s, m);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_commands() {
+        let cases = [
+            (mix(Link, M::Chat, S::Chat), "feedback_loop"),
+            (mix(Enable, M::Usb2, S::Usb2), "feedback_loop"),
+            (mix(Disable, M::Headphone1, S::CallMe1), "callme_disable_unsupported"),
+            (mix(Enable, M::Headphone1, S::CallMe2), "callme_enable_unsupported"),
+            (Command::MixCopy { from: M::Headphone1, to: M::Headphone1 }, "same_bus"),
+            (Command::MixSwap { first: M::Speaker, second: M::Speaker }, "same_bus"),
+            (Command::MixMinus { remotes: vec![S::Chat, S::Combo1] }, "no_return_bus"),
+            (bus(M::Headphone1, &[("nope", MixState::Linked)]), "unknown_source"),
+            (bus(M::Headphone1, &[("cm1", MixState::Disabled)]), "callme_disable_unsupported"),
+            (bus(M::Bluetooth, &[("bt", MixState::Unlinked)]), "feedback_loop"),
+        ];
+        for (command, code) in cases {
+            let err = command.validate().expect_err(&format!("{:?}", command));
+            assert_eq!(err.code(), code, "{:?}", command);
+            // `check` runs the same validation first
+            assert_eq!(command.check(&RoutingMatrix::default()).unwrap_err(), err);
+        }
+    }
+
+    #[test]
+    fn valid_commands() {
+        let cases = [
+            mix(Disable, M::Chat, S::Chat),
+            mix(Link, M::Headphone1, S::CallMe1),
+            mix(Unlink, M::Headphone1, S::CallMe1),
+            Command::MixCopy { from: M::Headphone1, to: M::Headphone2 },
+            Command::MixMinus { remotes: vec![S::Chat, S::Usb2, S::CallMe3] },
+            bus(M::Bluetooth, &[("bt", MixState::Disabled), ("*", MixState::Linked)]),
+            Command::Status,
+        ];
+        for command in cases {
+            assert_eq!(command.validate(), Ok(()), "{:?}", command);
+        }
+    }
+
+    #[test]
+    fn risky_routes_warn() {
+        let echo = |m, s| vec![CommandWarning::SharedDeviceEcho { mix: m, source: s }];
+        let cases = [
+            (mix(Link, M::Chat, S::Usb1), echo(M::Chat, S::Usb1)),
+            (mix(Unlink, M::Usb1, S::Chat), echo(M::Usb1, S::Chat)),
+            (mix(Disable, M::Usb1, S::Chat), vec![]),
+            (mix(Link, M::Usb2, S::Usb1), vec![]),
+        ];
+        for (command, warnings) in cases {
+            assert_eq!(command.check(&RoutingMatrix::default()).unwrap(), warnings, "{:?}", command);
+        }
+        assert_eq!(
+            check_route(M::Chat, S::Usb1, MixState::Unlinked).unwrap().map(|w| w.code()),
+            Some("shared_device_echo")
+        );
+    }
+
+    #[test]
+    fn unlink_loops_only_if_routed() {
+        let command = mix(Unlink, M::Chat, S::Chat);
+        assert_eq!(command.validate(), Ok(()));
+        // Unknown cells count as routed
+        assert_eq!(command.check(&RoutingMatrix::default()).unwrap_err().code(), "feedback_loop");
+
+        let mut matrix = RoutingMatrix::default();
+        matrix.apply(M::Chat, S::Chat, Disable);
+        assert_eq!(command.check(&matrix), Ok(vec![]));
+    }
+}
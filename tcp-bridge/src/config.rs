@@ -0,0 +1,459 @@
+//! Shared configuration for `tcp-bridge`, `api-server` and `bridge-ctl`.
+//!
+//! Read from a TOML file (`--config`, `BRIDGE_CONFIG`, default
+//! `/etc/bridge.toml`; a missing default file means built-in defaults),
+//! then overridden by environment variables named
+//! `BRIDGE_<SECTION>_<KEY>` (e.g. `BRIDGE_PROXY_BIND_PORT=9001`), then by
+//! `--setting section.key=value` and each binary's own flags.
+//!
+//! `tcp-bridge` reloads it on SIGHUP or `bridge-ctl reload`; see
+//! `Config::reload` for what changes live.
+//!
+//! ```toml
+//! [proxy]
+//! bind_ip = "127.0.0.2"
+//! bind_port = 9000
+//!
+//! [control]
+//! socket_mode = 0o660
+//!
+//! [api]
+//! bind = "0.0.0.0:8080"
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use crate::network::NetworkSetup;
+
+/// Not in /tmp, where anyone could put a config in place first
+pub const DEFAULT_CONFIG: &str = "/etc/bridge.toml";
+
+/// Prefix of the override variables; `BRIDGE_CONFIG` names the file
+const ENV_PREFIX: &str = "BRIDGE_";
+
+/// Settings `tcp-bridge` uses from the start (sockets, listener, frame
+/// codec, network setup); a reload keeps their current value
+pub const RESTART_REQUIRED: &[&str] = &[
+    "proxy.bind_ip",
+    "proxy.bind_port",
+    "proxy.target_ip",
+    "proxy.target_port",
+    "proxy.source_ip",
+    "proxy.max_frame_size",
+    "control.socket",
+    "control.handover_socket",
+    "features.setup_network",
+];
+
+/// Settings only `api-server` or `bridge-ctl` read
+pub const NOT_USED_BY_BRIDGE: &[&str] = &["api.bind", "api.allow_reload", "pacing.fade_step_ms"];
+
+/// Settings without a default value, so missing from `Config::default()`
+const UNSET_BY_DEFAULT: &[&str] = &["control.socket_mode"];
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub proxy: ProxyConfig,
+    pub control: ControlConfig,
+    pub api: ApiConfig,
+    pub pacing: PacingConfig,
+    pub logging: LoggingConfig,
+    pub features: FeatureConfig,
+}
+
+/// Where the proxy listens and what it connects to
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProxyConfig {
+    pub bind_ip: String,
+    pub bind_port: u16,
+    pub target_ip: String,
+    pub target_port: u16,
+    /// The proxy's own upstream connection comes from here
+    pub source_ip: String,
+    /// Maximum frame payload size; a larger frame closes the connection
+    pub max_frame_size: usize,
+    /// Flag the mixer as unhealthy when a ping stays unanswered this long
+    pub ping_timeout_ms: u64,
+    /// JSON file with declarative command definitions
+    pub spec: PathBuf,
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        ProxyConfig {
+            bind_ip: "127.0.0.2".to_string(),
+            bind_port: 9000,
+            target_ip: "127.0.0.1".to_string(),
+            target_port: 2345,
+            source_ip: "127.0.0.2".to_string(),
+            max_frame_size: crate::protocol::DEFAULT_MAX_FRAME_SIZE,
+            ping_timeout_ms: 5000,
+            spec: PathBuf::from("/tmp/commands.json"),
+        }
+    }
+}
+
+impl ProxyConfig {
+    /// The addresses as needed for the alias and DNAT rule
+    pub fn network_setup(&self) -> Result<NetworkSetup, String> {
+        let ip = |name: &str, value: &str| value.parse()
+            .map_err(|_| format!("proxy.{} must be an IPv4 address for network setup: {}", name, value));
+        Ok(NetworkSetup {
+            bind_ip: ip("bind_ip", &self.bind_ip)?,
+            bind_port: self.bind_port,
+            target_ip: ip("target_ip", &self.target_ip)?,
+            target_port: self.target_port,
+            source_ip: ip("source_ip", &self.source_ip)?,
+        })
+    }
+}
+
+/// Unix sockets of the bridge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ControlConfig {
+    /// Control socket used by `bridge-ctl` and `api-server`
+    pub socket: PathBuf,
+    /// Permissions of the control socket (e.g. `0o660`); umask if not set
+    pub socket_mode: Option<u32>,
+    /// Socket a new bridge takes over from (`--handover`)
+    pub handover_socket: PathBuf,
+}
+
+impl Default for ControlConfig {
+    fn default() -> Self {
+        ControlConfig {
+            socket: PathBuf::from("/tmp/socket_bridge_control"),
+            socket_mode: None,
+            handover_socket: PathBuf::from("/tmp/socket_bridge_handover"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ApiConfig {
+    /// Address the HTTP API listens on
+    pub bind: String,
+    /// Forward `reload` through `POST /command`; the API has no authentication
+    pub allow_reload: bool,
+}
+
+impl Default for ApiConfig {
+    fn default() -> Self {
+        ApiConfig { bind: "0.0.0.0:8080".to_string(), allow_reload: false }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PacingConfig {
+    /// Delay between the frames of a multi-frame command
+    pub packet_delay_ms: u64,
+    /// Interval of the level steps of `fade` in `bridge-ctl run`
+    pub fade_step_ms: u64,
+}
+
+impl Default for PacingConfig {
+    fn default() -> Self {
+        PacingConfig { packet_delay_ms: 50, fade_step_ms: 100 }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// Hexdump every frame (repeats collapsed) and every injected frame
+    pub hexdump: bool,
+    /// Bytes shown per hexdump; longer frames are truncated
+    pub hexdump_bytes: usize,
+}
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        LoggingConfig { hexdump: true, hexdump_bytes: 128 }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FeatureConfig {
+    /// Inject with the default session ID (01 01 01 01) before one is sniffed
+    pub allow_default_session: bool,
+    /// Re-inject the last command for each target after a session change
+    pub reapply_on_session_change: bool,
+    /// Add and remove the loopback alias and DNAT rule (`--setup-network`)
+    pub setup_network: bool,
+}
+
+impl Config {
+    /// Config file plus environment and `--setting` overrides
+    pub fn load(path: Option<&Path>, overrides: &[String]) -> Result<Config, String> {
+        let env_path = std::env::var_os("BRIDGE_CONFIG").map(PathBuf::from);
+        let (path, required) = match path.map(Path::to_path_buf).or(env_path) {
+            Some(path) => (path, true),
+            None => (PathBuf::from(DEFAULT_CONFIG), false),
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound && !required => String::new(),
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        // Variables that are not UTF-8 cannot name a setting
+        let env = std::env::vars_os()
+            .filter_map(|(name, value)| Some((name.into_string().ok()?, value.into_string().ok()?)));
+        Self::parse(&text, &path, env, overrides)
+    }
+
+    /// Config file contents with the environment and `--setting` applied
+    fn parse(
+        text: &str,
+        path: &Path,
+        env: impl IntoIterator<Item = (String, String)>,
+        overrides: &[String],
+    ) -> Result<Config, String> {
+        let mut table: toml::Table = text.parse()
+            .map_err(|e| format!("Invalid config {}: {}", path.display(), one_line(&e)))?;
+
+        let sections = Self::sections();
+        let settings = Self::settings();
+        for (name, value) in env {
+            let Some(rest) = name.strip_prefix(ENV_PREFIX) else { continue };
+            let rest = rest.to_lowercase();
+            let setting = settings.iter().find(|(section, key)| rest == format!("{}_{}", section, key));
+            match setting {
+                Some((section, key)) => set(&mut table, section, key, &value),
+                // Other BRIDGE_ variables (BRIDGE_CONFIG, BRIDGE_CTL_REMOTE) are not settings
+                None if sections.iter().any(|s| rest.starts_with(&format!("{}_", s))) => {
+                    eprintln!("[Config] Ignoring {}: not a setting", name);
+                }
+                None => {}
+            }
+        }
+        for assignment in overrides {
+            let (path, value) = assignment.split_once('=')
+                .ok_or_else(|| format!("Invalid --setting {} (expected section.key=value)", assignment))?;
+            let (section, key) = path.split_once('.')
+                .ok_or_else(|| format!("Invalid --setting {} (expected section.key=value)", assignment))?;
+            if !sections.iter().any(|s| s == section) {
+                return Err(format!("Unknown config section: {} (expected one of {})", section, sections.join(", ")));
+            }
+            set(&mut table, section, key, value);
+        }
+
+        table.try_into().map_err(|e| format!("Invalid config: {}", one_line(&e)))
+    }
+
+    /// Compare with a newly loaded config. The result has the new values
+    /// except for `RESTART_REQUIRED` settings, which keep the current ones.
+    pub fn reload(&self, new: &Config) -> Result<Reload, String> {
+        let table = |config: &Config| toml::Table::try_from(config).map_err(|e| format!("Invalid config: {}", e));
+        let (old, mut merged) = (table(self)?, table(new)?);
+        let mut reload = Reload::default();
+        for section in Self::sections() {
+            let keys: BTreeSet<&String> = [&old, &merged].into_iter()
+                .filter_map(|t| t.get(&section)?.as_table())
+                .flat_map(|t| t.keys())
+                .collect();
+            let changed: Vec<String> = keys.into_iter()
+                .filter(|key| setting(&old, &section, key) != setting(&merged, &section, key))
+                .cloned()
+                .collect();
+            for key in changed {
+                let path = format!("{}.{}", section, key);
+                if RESTART_REQUIRED.contains(&path.as_str()) {
+                    if let Some(toml::Value::Table(values)) = merged.get_mut(&section) {
+                        match setting(&old, &section, &key) {
+                            Some(value) => values.insert(key, value.clone()),
+                            None => values.remove(&key),
+                        };
+                    }
+                    reload.restart_required.push(path);
+                } else if NOT_USED_BY_BRIDGE.contains(&path.as_str()) {
+                    reload.not_used.push(path);
+                } else {
+                    reload.applied.push(path);
+                }
+            }
+        }
+        reload.config = merged.try_into().map_err(|e| format!("Invalid config: {}", one_line(&e)))?;
+        Ok(reload)
+    }
+
+    /// The effective configuration as TOML
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).unwrap_or_default()
+    }
+
+    fn sections() -> Vec<String> {
+        match toml::Table::try_from(Config::default()) {
+            Ok(table) => table.keys().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    /// Every setting as (section, key)
+    fn settings() -> Vec<(String, String)> {
+        let Ok(table) = toml::Table::try_from(Config::default()) else { return Vec::new() };
+        let mut settings: Vec<(String, String)> = table.iter()
+            .filter_map(|(section, values)| Some((section, values.as_table()?)))
+            .flat_map(|(section, values)| values.keys().map(|key| (section.clone(), key.clone())))
+            .collect();
+        settings.extend(UNSET_BY_DEFAULT.iter().filter_map(|path| {
+            let (section, key) = path.split_once('.')?;
+            Some((section.to_string(), key.to_string()))
+        }));
+        settings
+    }
+}
+
+/// Outcome of `Config::reload`; settings are given as `section.key`
+#[derive(Debug, Default)]
+pub struct Reload {
+    pub config: Config,
+    /// Changed and in effect once `config` is used
+    pub applied: Vec<String>,
+    /// Changed, but kept at the current value until a restart
+    pub restart_required: Vec<String>,
+    /// Changed, but only read by `api-server` or `bridge-ctl`
+    pub not_used: Vec<String>,
+}
+
+fn setting<'a>(table: &'a toml::Table, section: &str, key: &str) -> Option<&'a toml::Value> {
+    table.get(section)?.as_table()?.get(key)
+}
+
+/// Set `section.key`; the value is parsed as TOML, or taken as a string
+fn set(table: &mut toml::Table, section: &str, key: &str, value: &str) {
+    let value = format!("v = {}", value).parse::<toml::Table>().ok()
+        .and_then(|mut t| t.remove("v"))
+        .unwrap_or_else(|| toml::Value::String(value.to_string()));
+    let section = table.entry(section).or_insert_with(|| toml::Value::Table(toml::Table::new()));
+    if let toml::Value::Table(section) = section {
+        section.insert(key.to_string(), value);
+    }
+}
+
+/// toml errors span several lines
+fn one_line(e: &toml::de::Error) -> String {
+    e.to_string().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(text: &str, env: &[(&str, &str)], overrides: &[&str]) -> Result<Config, String> {
+        let env = env.iter().map(|(name, value)| (name.to_string(), value.to_string()));
+        let overrides: Vec<String> = overrides.iter().map(|o| o.to_string()).collect();
+        Config::parse(text, Path::new("test.toml"), env, &overrides)
+    }
+
+    #[test]
+    fn file_then_environment_then_settings() {
+        let file = "[proxy]\nbind_port = 9001\nping_timeout_ms = 1000\n[pacing]\npacket_delay_ms = 10";
+        let env = [("BRIDGE_PROXY_BIND_PORT", "9002"), ("BRIDGE_PACING_PACKET_DELAY_MS", "20")];
+
+        let config = parse(file, &[], &[]).unwrap();
+        assert_eq!((config.proxy.bind_port, config.pacing.packet_delay_ms), (9001, 10));
+
+        let config = parse(file, &env, &[]).unwrap();
+        assert_eq!((config.proxy.bind_port, config.pacing.packet_delay_ms), (9002, 20));
+        assert_eq!(config.proxy.ping_timeout_ms, 1000);
+
+        let config = parse(file, &env, &["proxy.bind_port=9003"]).unwrap();
+        assert_eq!((config.proxy.bind_port, config.pacing.packet_delay_ms), (9003, 20));
+
+        // Missing sections and keys keep their defaults
+        let config = parse("", &[], &[]).unwrap();
+        assert_eq!(config.proxy.bind_port, ProxyConfig::default().bind_port);
+    }
+
+    #[test]
+    fn values_are_toml_or_strings() {
+        let env = [("BRIDGE_CONTROL_SOCKET_MODE", "0o660"), ("BRIDGE_API_BIND", "127.0.0.1:8081")];
+        let config = parse("", &env, &["features.allow_default_session=true", "control.socket=/run/bridge"]).unwrap();
+        assert_eq!(config.control.socket_mode, Some(0o660));
+        assert_eq!(config.api.bind, "127.0.0.1:8081");
+        assert!(config.features.allow_default_session);
+        assert_eq!(config.control.socket, PathBuf::from("/run/bridge"));
+    }
+
+    #[test]
+    fn only_known_settings_come_from_the_environment() {
+        let env = [
+            ("BRIDGE_CONFIG", "/elsewhere.toml"),
+            ("BRIDGE_CTL_REMOTE", "http://host:8080"),
+            ("BRIDGE_PROXY_PID", "1234"),
+            ("BRIDGE_API_BIND_ADDRESS", "x"),
+            ("PROXY_BIND_PORT", "1"),
+            ("BRIDGE_FEATURES_SETUP_NETWORK", "true"),
+        ];
+        let config = parse("", &env, &[]).unwrap();
+        assert!(config.features.setup_network);
+        assert_eq!(config.to_toml(), {
+            let mut expected = Config::default();
+            expected.features.setup_network = true;
+            expected.to_toml()
+        });
+    }
+
+    #[test]
+    fn reload_sorts_changes() {
+        let old = parse("[proxy]\nbind_port = 9001", &[], &[]).unwrap();
+        let new = parse(
+            "[proxy]\nbind_port = 9002\nping_timeout_ms = 100\n\
+             [control]\nsocket_mode = 0o600\nsocket = \"/run/bridge\"\n\
+             [api]\nallow_reload = true\n[pacing]\nfade_step_ms = 50\n[logging]\nhexdump = false",
+            &[], &[],
+        ).unwrap();
+
+        let reload = old.reload(&new).unwrap();
+        assert_eq!(reload.applied, ["control.socket_mode", "logging.hexdump", "proxy.ping_timeout_ms"]);
+        assert_eq!(reload.restart_required, ["control.socket", "proxy.bind_port"]);
+        assert_eq!(reload.not_used, ["api.allow_reload", "pacing.fade_step_ms"]);
+
+        // Restart-only settings keep the running value, the rest is new
+        let config = reload.config;
+        assert_eq!(config.proxy.bind_port, 9001);
+        assert_eq!(config.control.socket, old.control.socket);
+        assert_eq!(config.proxy.ping_timeout_ms, 100);
+        assert_eq!(config.control.socket_mode, Some(0o600));
+        assert!(!config.logging.hexdump);
+        assert!(config.api.allow_reload);
+        assert_eq!(config.pacing.fade_step_ms, 50);
+    }
+
+    #[test]
+    fn reload_without_changes() {
+        let config = parse("[control]\nsocket_mode = 0o600", &[], &[]).unwrap();
+        let reload = config.reload(&config).unwrap();
+        assert!(reload.applied.is_empty() && reload.restart_required.is_empty() && reload.not_used.is_empty());
+
+        // Unsetting a setting counts as a change too
+        let reload = config.reload(&Config::default()).unwrap();
+        assert_eq!(reload.applied, ["control.socket_mode"]);
+        assert_eq!(reload.config.control.socket_mode, None);
+    }
+
+    #[test]
+    fn invalid_settings() {
+        let cases = [
+            (parse("[proxy]\nbind_prot = 1", &[], &[]), "Invalid config: unknown field `bind_prot`"),
+            (parse("[proxy", &[], &[]), "Invalid config test.toml:"),
+            (parse("", &[("BRIDGE_PROXY_BIND_PORT", "high")], &[]), "Invalid config: invalid type"),
+            (parse("", &[], &["proxy.bind_port"]), "Invalid --setting proxy.bind_port"),
+            (parse("", &[], &["bind_port=1"]), "Invalid --setting bind_port=1"),
+            (parse("", &[], &["proxi.bind_port=1"]), "Unknown config section: proxi"),
+            (parse("", &[], &["proxy.bind_prot=1"]), "Invalid config: unknown field `bind_prot`"),
+        ];
+        for (result, error) in cases {
+            let e = result.unwrap_err();
+            assert!(e.starts_with(error), "expected {:?}, got {:?}", error, e);
+        }
+    }
+}
@@ -0,0 +1,102 @@
+//! Fader strip state (mute, input source, level).
+//!
+//! Filled from `channelOutputMute`, `channelInputSource` and `faderLevel`
+//! frames in either direction, plus the bridge's own injected commands.
+
+use serde::{Deserialize, Serialize};
+
+use crate::names::{Fader, Source};
+use crate::protocol::{self, Message, RodeDecode};
+
+/// Full scale of `faderLevel`
+pub const LEVEL_MAX: u32 = 65535;
+
+/// One fader as returned for `Command::Faders`; `None` = not seen yet
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FaderStatus {
+    pub fader: Fader,
+    pub muted: Option<bool>,
+    /// Input source, if the ID maps to a known `Source`
+    pub source: Option<Source>,
+    /// Raw `channelInputSource` value
+    pub source_id: Option<u32>,
+    /// 0-65535
+    pub level: Option<u32>,
+}
+
+impl FaderStatus {
+    fn new(fader: Fader) -> Self {
+        Self { fader, muted: None, source: None, source_id: None, level: None }
+    }
+}
+
+/// All nine faders, indexed like `Fader::to_index`
+#[derive(Debug, Clone)]
+pub struct FaderBank {
+    faders: [FaderStatus; 9],
+}
+
+impl Default for FaderBank {
+    fn default() -> Self {
+        Self { faders: Fader::ALL.map(FaderStatus::new) }
+    }
+}
+
+impl FaderBank {
+    pub fn set_muted(&mut self, index: u8, muted: bool) {
+        if let Some(f) = self.faders.get_mut(index as usize) {
+            f.muted = Some(muted);
+        }
+    }
+
+    pub fn set_source(&mut self, index: u8, source_id: u32) {
+        if let Some(f) = self.faders.get_mut(index as usize) {
+            f.source_id = Some(source_id);
+            f.source = u8::try_from(source_id).ok().and_then(Source::from_index);
+        }
+    }
+
+    pub fn set_level(&mut self, index: u8, level: u32) {
+        if let Some(f) = self.faders.get_mut(index as usize) {
+            f.level = Some(level);
+        }
+    }
+
+    /// Update from a frame, returning the fader it touched
+    pub fn observe(&mut self, payload: &[u8]) -> Option<Fader> {
+        // faderLevel frames have their own preamble instead of a session ID
+        if let Some(level) = protocol::FaderLevel::decode_payload(payload) {
+            self.set_level(level.fader_index, level.level);
+            return Fader::from_index(level.fader_index);
+        }
+        let message = Message::parse(payload)?;
+        match message.property {
+            "channelOutputMute" => {
+                let mute = protocol::ChannelOutputMute::decode_payload(payload)?;
+                self.set_muted(mute.fader_index, mute.mute);
+                Fader::from_index(mute.fader_index)
+            }
+            "channelInputSource" => {
+                let source = protocol::ChannelInputSource::decode_payload(payload)?;
+                self.set_source(source.fader_index, source.source_id);
+                Fader::from_index(source.fader_index)
+            }
+            _ => None,
+        }
+    }
+
+    /// Rebuild from a snapshot (after a handover)
+    pub fn from_snapshot(snapshot: &[FaderStatus]) -> Self {
+        let mut bank = FaderBank::default();
+        for status in snapshot {
+            if let Some(f) = bank.faders.get_mut(status.fader.to_index() as usize) {
+                *f = *status;
+            }
+        }
+        bank
+    }
+
+    pub fn snapshot(&self) -> Vec<FaderStatus> {
+        self.faders.to_vec()
+    }
+}
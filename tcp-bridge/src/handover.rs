@@ -0,0 +1,257 @@
+//! `--handover`: replace a running bridge without dropping the GUI.
+//!
+//! Every bridge listens on `control.handover_socket`. A new one started with
+//! `--handover` connects there. The old one stops accepting, parks each
+//! connection between two frames and passes the listening socket and the
+//! client/server socket pairs over with `SCM_RIGHTS`. The sniffed state
+//! and the bytes of partly read frames follow as JSON. The new process
+//! confirms and carries on forwarding on the same sockets, so neither the
+//! GUI nor the mixer sees a disconnect; the old one exits with
+//! `EXIT_HANDED_OVER`. If the new process does not confirm, the old one
+//! resumes forwarding.
+
+use serde::{Deserialize, Serialize};
+use std::io::{self, Read, Write};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream, UnixListener};
+use tokio::sync::mpsc;
+
+use tcp_bridge::faders::FaderStatus;
+use tcp_bridge::inject::ProxyCommand;
+use tcp_bridge::protocol::CallMeLayout;
+use tcp_bridge::routing::MatrixSnapshot;
+use tcp_bridge::status::SessionEvent;
+
+/// Exit code of a bridge that handed over (EX_TEMPFAIL, not used otherwise)
+pub const EXIT_HANDED_OVER: i32 = 75;
+
+/// Listener plus two sockets per connection
+const MAX_FDS: usize = 64;
+
+/// How long the old process waits for the new one to confirm
+const CONFIRM_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// How long the old process waits for its connections to park
+pub const PARK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A client/server socket pair, with the bytes of partly read frames
+pub struct Connection {
+    pub client: TcpStream,
+    pub server: TcpStream,
+    pub client_buffered: Vec<u8>,
+    pub server_buffered: Vec<u8>,
+}
+
+impl Connection {
+    pub fn new(client: TcpStream, server: TcpStream) -> Self {
+        Connection { client, server, client_buffered: Vec::new(), server_buffered: Vec::new() }
+    }
+}
+
+/// Sniffed state carried over to the new process
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SessionState {
+    pub session_id: Option<String>,
+    pub callme_session: Option<String>,
+    #[serde(default)]
+    pub callme_layout: CallMeLayout,
+    pub session_changes: u64,
+    pub last_session_change: Option<SessionEvent>,
+    pub routing: MatrixSnapshot,
+    pub faders: Vec<FaderStatus>,
+    /// Re-apply list of `--reapply-on-session-change`
+    #[serde(default)]
+    pub overrides: Vec<(Vec<String>, ProxyCommand)>,
+}
+
+/// What follows the file descriptors on the handover socket
+#[derive(Serialize, Deserialize)]
+struct Snapshot {
+    state: SessionState,
+    /// Hex bytes of partly read frames, in the order of the socket pairs
+    buffered: Vec<(String, String)>,
+}
+
+/// Everything taken over from the old process
+pub struct TakenOver {
+    pub listener: TcpListener,
+    pub connections: Vec<Connection>,
+    pub state: SessionState,
+    pub confirmation: Confirmation,
+}
+
+/// Sent once the new process is ready; until then the old one can resume
+pub struct Confirmation(UnixStream);
+
+impl Confirmation {
+    pub fn send(mut self) -> io::Result<()> {
+        self.0.write_all(b"1")
+    }
+}
+
+/// Accept handover requests and pass them on to the accept loop
+pub fn listen(path: &Path) -> io::Result<mpsc::Receiver<tokio::net::UnixStream>> {
+    let _ = std::fs::remove_file(path);
+    let listener = UnixListener::bind(path)?;
+    let (tx, rx) = mpsc::channel(1);
+    tokio::spawn(async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    if tx.send(stream).await.is_err() {
+                        return;
+                    }
+                }
+                Err(e) => eprintln!("[Handover] Accept error: {}", e),
+            }
+        }
+    });
+    Ok(rx)
+}
+
+/// Old process: send the listener, the parked connections and the state,
+/// then wait until the new process confirms
+pub fn hand_over(
+    peer: tokio::net::UnixStream,
+    listener: &TcpListener,
+    connections: &[Connection],
+    state: SessionState,
+) -> io::Result<()> {
+    let mut peer = peer.into_std()?;
+    peer.set_nonblocking(false)?;
+
+    let mut fds = vec![listener.as_raw_fd()];
+    for conn in connections {
+        fds.push(conn.client.as_raw_fd());
+        fds.push(conn.server.as_raw_fd());
+    }
+    send_fds(&peer, &fds)?;
+
+    let snapshot = Snapshot {
+        state,
+        buffered: connections.iter()
+            .map(|c| (hex::encode(&c.client_buffered), hex::encode(&c.server_buffered)))
+            .collect(),
+    };
+    peer.write_all(&serde_json::to_vec(&snapshot)?)?;
+    peer.shutdown(std::net::Shutdown::Write)?;
+
+    peer.set_read_timeout(Some(CONFIRM_TIMEOUT))?;
+    let mut confirm = [0u8; 1];
+    peer.read_exact(&mut confirm)
+        .map_err(|e| io::Error::new(e.kind(), format!("new process did not confirm: {}", e)))
+}
+
+/// New process: receive everything from the running bridge
+pub fn take_over(path: &Path) -> io::Result<TakenOver> {
+    let mut peer = UnixStream::connect(path)
+        .map_err(|e| io::Error::new(e.kind(), format!("no bridge to take over from on {}: {}", path.display(), e)))?;
+    let mut fds = recv_fds(&peer)?.into_iter();
+
+    let mut json = Vec::new();
+    peer.read_to_end(&mut json)?;
+    let snapshot: Snapshot = serde_json::from_slice(&json)?;
+
+    let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidData, msg.to_string());
+    let listener = fds.next().ok_or_else(|| invalid("no listening socket received"))?;
+    let listener = std::net::TcpListener::from(listener);
+    listener.set_nonblocking(true)?;
+    let listener = TcpListener::from_std(listener)?;
+
+    let mut connections = Vec::new();
+    for (client_buffered, server_buffered) in snapshot.buffered {
+        let (Some(client), Some(server)) = (fds.next(), fds.next()) else {
+            return Err(invalid("fewer sockets than connections received"));
+        };
+        connections.push(Connection {
+            client: tcp_stream(client)?,
+            server: tcp_stream(server)?,
+            client_buffered: hex::decode(client_buffered).map_err(|_| invalid("bad buffered bytes"))?,
+            server_buffered: hex::decode(server_buffered).map_err(|_| invalid("bad buffered bytes"))?,
+        });
+    }
+    Ok(TakenOver { listener, connections, state: snapshot.state, confirmation: Confirmation(peer) })
+}
+
+fn tcp_stream(fd: OwnedFd) -> io::Result<TcpStream> {
+    let stream = std::net::TcpStream::from(fd);
+    stream.set_nonblocking(true)?;
+    TcpStream::from_std(stream)
+}
+
+/// One `sendmsg` with the descriptor count as data and the descriptors as `SCM_RIGHTS`
+fn send_fds(socket: &UnixStream, fds: &[RawFd]) -> io::Result<()> {
+    if fds.len() > MAX_FDS {
+        return Err(io::Error::other(format!("too many sockets to hand over ({})", fds.len())));
+    }
+    let count = (fds.len() as u32).to_le_bytes();
+    let mut iov = libc::iovec { iov_base: count.as_ptr() as *mut _, iov_len: count.len() };
+    let fds_len = std::mem::size_of_val(fds) as u32;
+    // SAFETY: CMSG_* only compute sizes and offsets inside `control`
+    let mut control = vec![0u8; unsafe { libc::CMSG_SPACE(fds_len) } as usize];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut _;
+    msg.msg_controllen = control.len() as _;
+    // SAFETY: `control` has room for one header plus `fds`
+    let sent = unsafe {
+        let cmsg = libc::CMSG_FIRSTHDR(&msg);
+        (*cmsg).cmsg_level = libc::SOL_SOCKET;
+        (*cmsg).cmsg_type = libc::SCM_RIGHTS;
+        (*cmsg).cmsg_len = libc::CMSG_LEN(fds_len) as _;
+        std::ptr::copy_nonoverlapping(fds.as_ptr(), libc::CMSG_DATA(cmsg) as *mut RawFd, fds.len());
+        libc::sendmsg(socket.as_raw_fd(), &msg, 0)
+    };
+    match sent {
+        n if n < 0 => Err(io::Error::last_os_error()),
+        n if n as usize != count.len() => Err(io::Error::other("short handover header")),
+        _ => Ok(()),
+    }
+}
+
+/// Counterpart of `send_fds`; the descriptors are close-on-exec
+fn recv_fds(socket: &UnixStream) -> io::Result<Vec<OwnedFd>> {
+    let mut count = [0u8; 4];
+    let mut iov = libc::iovec { iov_base: count.as_mut_ptr() as *mut _, iov_len: count.len() };
+    // SAFETY: size computation only
+    let mut control = vec![0u8; unsafe { libc::CMSG_SPACE((MAX_FDS * std::mem::size_of::<RawFd>()) as u32) } as usize];
+    let mut msg: libc::msghdr = unsafe { std::mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = control.as_mut_ptr() as *mut _;
+    msg.msg_controllen = control.len() as _;
+    // SAFETY: `msg` points at live buffers of the given sizes
+    let received = unsafe { libc::recvmsg(socket.as_raw_fd(), &mut msg, libc::MSG_CMSG_CLOEXEC) };
+    if received < 0 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut fds = Vec::new();
+    // SAFETY: walks the control messages the kernel filled in; each
+    // SCM_RIGHTS descriptor is new in this process and owned from here on
+    unsafe {
+        let mut cmsg = libc::CMSG_FIRSTHDR(&msg);
+        while !cmsg.is_null() {
+            if (*cmsg).cmsg_level == libc::SOL_SOCKET && (*cmsg).cmsg_type == libc::SCM_RIGHTS {
+                let data = libc::CMSG_DATA(cmsg) as *const RawFd;
+                let n = ((*cmsg).cmsg_len as usize - libc::CMSG_LEN(0) as usize) / std::mem::size_of::<RawFd>();
+                for i in 0..n {
+                    fds.push(OwnedFd::from_raw_fd(std::ptr::read_unaligned(data.add(i))));
+                }
+            }
+            cmsg = libc::CMSG_NXTHDR(&msg, cmsg);
+        }
+    }
+    if received as usize != count.len() || msg.msg_flags & libc::MSG_CTRUNC != 0 {
+        return Err(io::Error::other("incomplete handover header"));
+    }
+    let expected = u32::from_le_bytes(count) as usize;
+    if fds.len() != expected {
+        return Err(io::Error::other(format!("expected {} sockets, received {}", expected, fds.len())));
+    }
+    Ok(fds)
+}
@@ -0,0 +1,228 @@
+//! What the bridge injects: JSON commands turned into `ProxyCommand`s and
+//! their wire frames. Shared by the bridge and `bridge-ctl encode`.
+
+use crate::commands::{Command, CommandError, CommandWarning, MixAction};
+use crate::names::{MixOutput, Source};
+use crate::protocol::{self, RodeCommand, CallMeChannel, MixCommand, MixError, CommandSpecs, Packet, SpecInvocation};
+use crate::routing::{self, BusPlan, RoutingMatrix};
+use serde::{Deserialize, Serialize};
+
+/// Internal command representation for the proxy
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ProxyCommand {
+    Mute { fader_index: u8, mute: bool },
+    Source { fader_index: u8, source_id: u32 },
+    MicType { fader_index: u8, mic_type: u32 },
+    Level { fader_index: u8, level: u32 },
+    Touch,
+    Mix { action: MixAction, mix_index: u8, source: Source },
+    Spec(SpecInvocation),
+    /// Several commands injected back to back with the usual packet pacing
+    Batch(Vec<ProxyCommand>),
+}
+
+/// A JSON command turned into what the proxy would inject
+pub struct Prepared {
+    pub commands: Vec<ProxyCommand>,
+    pub warnings: Vec<CommandWarning>,
+    /// Per-bus results of a routing request
+    pub plans: Vec<BusPlan>,
+}
+
+/// Check a JSON command against a routing matrix and convert it.
+/// Routing requests are diffed against the matrix.
+pub fn prepare(cmd: &Command, specs: &CommandSpecs, matrix: &RoutingMatrix) -> Result<Prepared, CommandError> {
+    if cmd.is_routing() {
+        return prepare_routing(cmd, matrix);
+    }
+    let warnings = cmd.check(matrix)?;
+    Ok(Prepared { commands: convert_command(cmd.clone(), specs)?, warnings, plans: Vec::new() })
+}
+
+/// Diff a routing request against the routing matrix; the changed cells
+/// become one batch, so they are paced like a multi-packet command
+fn prepare_routing(cmd: &Command, matrix: &RoutingMatrix) -> Result<Prepared, CommandError> {
+    cmd.validate()?;
+    let (plans, mut warnings) = match cmd {
+        Command::Bus { mix, routes } => (vec![routing::plan_bus(matrix, *mix, routes)?], Vec::new()),
+        Command::MixCopy { from, to } => (vec![routing::plan_copy(matrix, *from, *to)?], Vec::new()),
+        Command::MixSwap { first, second } => (routing::plan_swap(matrix, *first, *second)?, Vec::new()),
+        Command::MixMinus { remotes } => routing::plan_mix_minus(matrix, remotes)?,
+        _ => (Vec::new(), Vec::new()),
+    };
+    warnings.extend(plans.iter().flat_map(|plan| plan.warnings.iter().cloned()));
+
+    let batch: Vec<ProxyCommand> = plans.iter()
+        .flat_map(|plan| plan.actions.iter().map(|&(source, action)| ProxyCommand::Mix {
+            action, mix_index: plan.result.mix.to_index(), source,
+        }))
+        .collect();
+    let commands = if batch.is_empty() { Vec::new() } else { vec![ProxyCommand::Batch(batch)] };
+    Ok(Prepared { commands, warnings, plans })
+}
+
+/// Resolve a spec command (plus follow-ups)
+pub fn resolve_spec(specs: &CommandSpecs, name: &str, index: u8, value: Option<i64>) -> Result<Vec<ProxyCommand>, CommandError> {
+    specs.resolve(name, index, value)
+        .map(|steps| steps.into_iter().map(ProxyCommand::Spec).collect())
+        .map_err(|message| CommandError::InvalidSpec { message })
+}
+
+/// Convert a Command to one or more ProxyCommands
+pub fn convert_command(cmd: Command, specs: &CommandSpecs) -> Result<Vec<ProxyCommand>, CommandError> {
+    Ok(match cmd {
+        Command::Mix { action, mix, source } => {
+            vec![ProxyCommand::Mix { action, mix_index: mix.to_index(), source }]
+        }
+        Command::Fader { fader, muted, source, level } => {
+            let mut cmds = Vec::new();
+            let idx = fader.to_index();
+            
+            // Add all specified fields as separate commands
+            if let Some(m) = muted {
+                cmds.push(ProxyCommand::Mute { fader_index: idx, mute: m });
+            }
+            if let Some(s) = source {
+                cmds.push(ProxyCommand::Source { fader_index: idx, source_id: s.to_index() as u32 });
+            }
+            if let Some(l) = level {
+                let level_val = (l.clamp(0.0, 1.0) * 65535.0) as u32;
+                cmds.push(ProxyCommand::Level { fader_index: idx, level: level_val });
+            }
+            cmds
+        }
+        Command::RawSource { fader, source_id } => {
+            let fader_index = fader.to_index();
+            // Sent as one batch so the UI fix follows the source change directly
+            vec![ProxyCommand::Batch(vec![
+                ProxyCommand::Source { fader_index, source_id },
+                ProxyCommand::MicType { fader_index, mic_type: -1i32 as u32 },
+                ProxyCommand::MicType { fader_index, mic_type: 4 },
+            ])]
+        }
+        Command::Touch => vec![ProxyCommand::Touch],
+        // Answered in start_listener
+        Command::Status
+        | Command::Matrix
+        | Command::Faders
+        | Command::Traffic { .. }
+        | Command::Render { .. }
+        | Command::Reload
+        | Command::Bus { .. }
+        | Command::MixCopy { .. }
+        | Command::MixSwap { .. }
+        | Command::MixMinus { .. } => Vec::new(),
+        Command::Spec { name, index, value } => resolve_spec(specs, &name, index, value)?,
+    })
+}
+
+/// Complete frames (header included) for a list of commands
+pub fn frames(commands: &[ProxyCommand], session_id: &[u8], callme: Option<&CallMeChannel>) -> Result<Vec<Vec<u8>>, MixError> {
+    let mut frames = Vec::new();
+    for cmd in commands {
+        frames.extend(cmd.build_payloads(session_id, callme)?.into_iter().map(|p| Packet::new(p).to_bytes()));
+    }
+    Ok(frames)
+}
+
+impl ProxyCommand {
+    /// Same checks as `Command::check`, for commands that did not come as JSON
+    pub fn check(&self, matrix: &RoutingMatrix) -> Result<Vec<CommandWarning>, CommandError> {
+        match self {
+            ProxyCommand::Mix { action, mix_index, source } => match MixOutput::from_index(*mix_index) {
+                Some(mix) => Command::Mix { action: *action, mix, source: *source }.check(matrix),
+                None => Ok(Vec::new()),
+            },
+            ProxyCommand::Batch(cmds) => {
+                let mut warnings = Vec::new();
+                for cmd in cmds {
+                    warnings.extend(cmd.check(matrix)?);
+                }
+                Ok(warnings)
+            }
+            _ => Ok(Vec::new()),
+        }
+    }
+
+    pub fn needs_callme_session(&self) -> bool {
+        match self {
+            ProxyCommand::Mix { source, .. } => source.is_callme(),
+            ProxyCommand::Batch(cmds) => cmds.iter().any(ProxyCommand::needs_callme_session),
+            _ => false,
+        }
+    }
+
+    /// The properties this command sets, so a later command setting the
+    /// same ones replaces it. Empty for one-shot commands.
+    pub fn target_keys(&self) -> Vec<String> {
+        match self {
+            ProxyCommand::Mute { fader_index, .. } => vec![format!("mute:{}", fader_index)],
+            ProxyCommand::Source { fader_index, .. } => vec![format!("source:{}", fader_index)],
+            ProxyCommand::Level { fader_index, .. } => vec![format!("level:{}", fader_index)],
+            ProxyCommand::Mix { action, mix_index, source } => {
+                // mixDisabled and the link mode of a cell are separate properties
+                let disabled = format!("mix_disabled:{}:{}", mix_index, source);
+                let link = format!("mix_link:{}:{}", mix_index, source);
+                match action {
+                    // Sends enable before the link request
+                    MixAction::Link if !source.is_callme() => vec![disabled, link],
+                    MixAction::Link | MixAction::Unlink => vec![link],
+                    MixAction::Enable | MixAction::Disable => vec![disabled],
+                }
+            }
+            ProxyCommand::Spec(inv) => vec![format!("spec:{}:{}", inv.spec.property, inv.index)],
+            // MicType only follows a source change; Touch has no state;
+            // batches are recorded per command
+            ProxyCommand::MicType { .. } | ProxyCommand::Touch | ProxyCommand::Batch(_) => Vec::new(),
+        }
+    }
+
+    /// Build all payloads for this command (some commands need multiple packets)
+    pub fn build_payloads(&self, session_id: &[u8], callme: Option<&CallMeChannel>) -> Result<Vec<Vec<u8>>, MixError> {
+        match self {
+            ProxyCommand::Mix { action, mix_index, source } => {
+                MixCommand::new(*action, *mix_index, *source).build_payloads(session_id, callme)
+            }
+            ProxyCommand::Batch(cmds) => {
+                let mut payloads = Vec::new();
+                for cmd in cmds {
+                    payloads.extend(cmd.build_payloads(session_id, callme)?);
+                }
+                Ok(payloads)
+            }
+            _ => Ok(vec![self.build_payload(session_id)]),
+        }
+    }
+}
+
+impl RodeCommand for ProxyCommand {
+    fn build_payload(&self, session_id: &[u8]) -> Vec<u8> {
+        match self {
+            ProxyCommand::Mute { fader_index, mute } => {
+                protocol::ChannelOutputMute { fader_index: *fader_index, mute: *mute }
+                    .build_payload(session_id)
+            }
+            ProxyCommand::Source { fader_index, source_id } => {
+                protocol::ChannelInputSource { fader_index: *fader_index, source_id: *source_id }
+                    .build_payload(session_id)
+            }
+            ProxyCommand::MicType { fader_index, mic_type } => {
+                protocol::InputMicrophoneType { fader_index: *fader_index, mic_type: *mic_type }
+                    .build_payload(session_id)
+            }
+            ProxyCommand::Level { fader_index, level } => {
+                protocol::FaderLevel { fader_index: *fader_index, level: *level }
+                    .build_payload(session_id)
+            }
+            ProxyCommand::Touch => {
+                protocol::ScreenTouched.build_payload(session_id)
+            }
+            ProxyCommand::Mix { action, mix_index, source } => {
+                MixCommand::new(*action, *mix_index, *source).build_payload(session_id)
+            }
+            ProxyCommand::Spec(invocation) => invocation.build_payload(session_id),
+            // Batches have no single payload; build_payloads expands them
+            ProxyCommand::Batch(_) => Vec::new(),
+        }
+    }
+}
@@ -1,4 +1,15 @@
 // Library crate - shared types for all binaries
+
+// Lets derive-generated `::tcp_bridge::...` paths resolve inside this crate
+extern crate self as tcp_bridge;
+
 pub mod names;
 pub mod protocol;
 pub mod commands;
+pub mod status;
+pub mod routing;
+pub mod faders;
+pub mod traffic;
+pub mod inject;
+pub mod network;
+pub mod config;
@@ -2,44 +2,57 @@
 //! Uses JSON for type-safe command parsing.
 
 use tokio::net::UnixListener;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::sync::broadcast;
+use std::os::unix::fs::PermissionsExt;
 
-use crate::commands::{Command, MixAction};
+use crate::commands::{Command, CommandError, CommandReply, MixAction, RenderReply};
 use crate::names::Source;
-use crate::protocol::{self, RodeCommand, MixCommand};
-
-/// Internal command representation for the proxy
-#[derive(Clone, Debug)]
-pub enum ProxyCommand {
-    Mute { fader_index: u8, mute: bool },
-    Source { fader_index: u8, source_id: u32 },
-    MicType { fader_index: u8, mic_type: u32 },
-    Level { fader_index: u8, level: u32 },
-    Touch,
-    Mix { action: MixAction, mix_index: u8, source: Source },
-}
+use crate::protocol::{self, CommandSpecs};
+use crate::routing::RoutingReply;
+use crate::reload::{self, Loader};
+use crate::state::SharedState;
+use tcp_bridge::config::ControlConfig;
+use tcp_bridge::inject::{self, Prepared, ProxyCommand, resolve_spec};
 
-pub async fn start_listener(tx: broadcast::Sender<ProxyCommand>) {
-    let sock_path = "/tmp/socket_bridge_control";
+pub async fn start_listener(tx: broadcast::Sender<ProxyCommand>, state: SharedState, control: ControlConfig, load: Loader) {
+    let sock_path = &control.socket;
     let _ = std::fs::remove_file(sock_path);
     
     let listener = UnixListener::bind(sock_path).expect("Failed to bind control socket");
-    println!("[Listener] Listening on {} (JSON mode)", sock_path);
+    set_socket_mode(&control);
+    println!("[Listener] Listening on {} (JSON mode)", sock_path.display());
     
     loop {
         match listener.accept().await {
             Ok((mut stream, _addr)) => {
                 let tx = tx.clone();
+                let state = state.clone();
+                let load = load.clone();
                 tokio::spawn(async move {
                     let mut buf = String::new();
-                    if stream.read_to_string(&mut buf).await.is_ok() {
-                        let commands = parse_commands(&buf);
-                        for cmd in commands {
-                            println!("[Listener] Received: {:?}", cmd);
-                            let _ = tx.send(cmd);
-                        }
+                    if stream.read_to_string(&mut buf).await.is_err() {
+                        return;
                     }
+                    // Taken per command, so a reload applies to the next one
+                    let specs = state.lock().unwrap().specs.clone();
+                    // JSON commands are answered on the same stream
+                    let reply = match serde_json::from_str::<Command>(&buf) {
+                        Ok(Command::Status) => serde_json::to_string(&state.lock().unwrap().status()),
+                        Ok(Command::Matrix) => serde_json::to_string(&state.lock().unwrap().routing.snapshot()),
+                        Ok(Command::Faders) => serde_json::to_string(&state.lock().unwrap().faders.snapshot()),
+                        Ok(Command::Traffic { since }) => serde_json::to_string(&state.lock().unwrap().traffic.snapshot(since)),
+                        Ok(Command::Render { command }) => serde_json::to_string(&render(&command, &specs, &state)),
+                        Ok(Command::Reload) => serde_json::to_string(&reload::reload(&load, &state)),
+                        Ok(cmd) if cmd.is_routing() => serde_json::to_string(&apply_routing(&cmd, &tx, &specs, &state)),
+                        Ok(cmd) => serde_json::to_string(&apply_command(&cmd, &tx, &specs, &state)),
+                        Err(_) => {
+                            apply_legacy(&buf, &tx, &specs, &state);
+                            return;
+                        }
+                    };
+                    // The client may not wait for the reply
+                    let _ = stream.write_all(reply.unwrap_or_default().as_bytes()).await;
                 });
             }
             Err(e) => eprintln!("[Listener] Accept error: {}", e),
@@ -47,46 +60,157 @@ pub async fn start_listener(tx: broadcast::Sender<ProxyCommand>) {
     }
 }
 
-/// Parse input and return zero or more commands
-fn parse_commands(input: &str) -> Vec<ProxyCommand> {
-    // Try JSON first
-    if let Ok(cmd) = serde_json::from_str::<Command>(input) {
-        return convert_command(cmd);
+/// Apply `control.socket_mode` to the control socket (umask if not set)
+pub fn set_socket_mode(control: &ControlConfig) {
+    if let Some(mode) = control.socket_mode
+        && let Err(e) = std::fs::set_permissions(&control.socket, std::fs::Permissions::from_mode(mode))
+    {
+        eprintln!("[Listener] Cannot set mode {:o} on {}: {}", mode, control.socket.display(), e);
     }
-    
-    // Fallback to legacy string format
-    parse_legacy_command(input).into_iter().collect()
 }
 
-/// Convert a Command to one or more ProxyCommands
-fn convert_command(cmd: Command) -> Vec<ProxyCommand> {
-    match cmd {
-        Command::Mix { action, mix, source } => {
-            vec![ProxyCommand::Mix { action, mix_index: mix.to_index(), source }]
+/// Check a JSON command against the bridge state and convert it
+fn prepare(cmd: &Command, specs: &CommandSpecs, state: &SharedState) -> Result<Prepared, CommandError> {
+    let state = state.lock().unwrap();
+    let prepared = inject::prepare(cmd, specs, &state.routing)?;
+    if state.callme_channel().is_none() && prepared.commands.iter().any(ProxyCommand::needs_callme_session) {
+        return Err(CommandError::CallMeSessionUnknown);
+    }
+    Ok(prepared)
+}
+
+fn apply_routing(cmd: &Command, tx: &broadcast::Sender<ProxyCommand>, specs: &CommandSpecs, state: &SharedState) -> RoutingReply {
+    let refuse = |error: CommandError| {
+        eprintln!("[Listener] Routing refused: {}", error);
+        RoutingReply { error: Some(error), ..Default::default() }
+    };
+    let prepared = match prepare(cmd, specs, state) {
+        Ok(prepared) => prepared,
+        Err(e) => return refuse(e),
+    };
+    // Nothing to send is fine without a session; the reply just says so
+    if !prepared.commands.is_empty()
+        && let Err(e) = state.lock().unwrap().ready_to_inject()
+    {
+        return refuse(e);
+    }
+
+    for plan in &prepared.plans {
+        println!("[Listener] Bus {}: {} cells changed, {} skipped",
+            plan.result.mix, plan.result.changed.len(), plan.result.skipped.len());
+    }
+    for warning in &prepared.warnings {
+        eprintln!("[Listener] Warning: {}", warning);
+    }
+    for cmd in prepared.commands {
+        let _ = tx.send(cmd);
+    }
+    RoutingReply {
+        buses: prepared.plans.into_iter().map(|p| p.result).collect(),
+        warnings: prepared.warnings,
+        error: None,
+    }
+}
+
+/// Check a JSON command against the bridge state and queue it
+fn apply_command(cmd: &Command, tx: &broadcast::Sender<ProxyCommand>, specs: &CommandSpecs, state: &SharedState) -> CommandReply {
+    let refuse = |error: CommandError| {
+        eprintln!("[Listener] Rejected: {}", error);
+        CommandReply { error: Some(error), ..Default::default() }
+    };
+    let prepared = match prepare(cmd, specs, state) {
+        Ok(prepared) => prepared,
+        Err(e) => return refuse(e),
+    };
+    if let Err(e) = state.lock().unwrap().ready_to_inject() {
+        return refuse(e);
+    }
+
+    for warning in &prepared.warnings {
+        eprintln!("[Listener] Warning: {}", warning);
+    }
+    for cmd in prepared.commands {
+        println!("[Listener] Received: {:?}", cmd);
+        let _ = tx.send(cmd);
+    }
+    CommandReply { warnings: prepared.warnings, error: None }
+}
+
+/// Build the frames a command would inject, without sending anything
+fn render(cmd: &Command, specs: &CommandSpecs, state: &SharedState) -> RenderReply {
+    let prepared = match prepare(cmd, specs, state) {
+        Ok(prepared) => prepared,
+        Err(e) => return RenderReply { error: Some(e), ..Default::default() },
+    };
+    let (session, callme) = {
+        let state = state.lock().unwrap();
+        (state.session_id.clone(), state.callme_channel())
+    };
+    let session_id = session.clone().unwrap_or_else(|| protocol::DEFAULT_SESSION_ID.to_vec());
+
+    // CallMe session and disable support were checked in `prepare`
+    let frames = inject::frames(&prepared.commands, &session_id, callme.as_ref()).unwrap_or_else(|e| {
+        eprintln!("[Listener] Cannot render {:?}: {}", cmd, e);
+        Vec::new()
+    });
+    RenderReply {
+        frames: frames.iter().map(hex::encode).collect(),
+        session_id: hex::encode(&session_id),
+        session_known: session.is_some(),
+        warnings: prepared.warnings,
+        error: None,
+    }
+}
+
+/// Legacy text commands get the same checks but no reply
+fn apply_legacy(input: &str, tx: &broadcast::Sender<ProxyCommand>, specs: &CommandSpecs, state: &SharedState) {
+    let commands = parse_legacy_command(input, specs);
+    {
+        let state = state.lock().unwrap();
+        if let Err(e) = state.ready_to_inject()
+            && !commands.is_empty()
+        {
+            eprintln!("[Listener] Refused: {}", e);
+            return;
         }
-        Command::Fader { fader, muted, source, level } => {
-            let mut cmds = Vec::new();
-            let idx = fader.to_index();
-            
-            // Add all specified fields as separate commands
-            if let Some(m) = muted {
-                cmds.push(ProxyCommand::Mute { fader_index: idx, mute: m });
-            }
-            if let Some(s) = source {
-                cmds.push(ProxyCommand::Source { fader_index: idx, source_id: s.to_index() as u32 });
-            }
-            if let Some(l) = level {
-                let level_val = (l.clamp(0.0, 1.0) * 65535.0) as u32;
-                cmds.push(ProxyCommand::Level { fader_index: idx, level: level_val });
+        if state.callme_channel().is_none() && commands.iter().any(ProxyCommand::needs_callme_session) {
+            eprintln!("[Listener] Refused: {}", CommandError::CallMeSessionUnknown);
+            return;
+        }
+        for cmd in &commands {
+            if let Err(e) = cmd.check(&state.routing) {
+                eprintln!("[Listener] Rejected: {}", e);
+                return;
             }
-            cmds
         }
-        Command::Touch => vec![ProxyCommand::Touch],
+    }
+    for cmd in commands {
+        println!("[Listener] Received: {:?}", cmd);
+        let _ = tx.send(cmd);
     }
 }
 
-fn parse_legacy_command(input: &str) -> Option<ProxyCommand> {
-    let parts: Vec<&str> = input.trim().split_whitespace().collect();
+fn parse_legacy_command(input: &str, specs: &CommandSpecs) -> Vec<ProxyCommand> {
+    let parts: Vec<&str> = input.split_whitespace().collect();
+
+    // spec <name> <index> [value]
+    if parts.first() == Some(&"spec") && parts.len() >= 3 {
+        let Ok(index) = parts[2].parse() else { return Vec::new() };
+        let value = match parts.get(3).map(|v| v.parse()) {
+            Some(Ok(v)) => Some(v),
+            Some(Err(_)) => return Vec::new(),
+            None => None,
+        };
+        return resolve_spec(specs, parts[1], index, value).unwrap_or_else(|e| {
+            eprintln!("[Listener] {}", e);
+            Vec::new()
+        });
+    }
+
+    parse_legacy_single(&parts).into_iter().collect()
+}
+
+fn parse_legacy_single(parts: &[&str]) -> Option<ProxyCommand> {
     if parts.is_empty() { return None; }
     
     match parts[0] {
@@ -124,6 +248,15 @@ fn parse_legacy_command(input: &str) -> Option<ProxyCommand> {
                 source: index_to_source(source_index)? 
             })
         }
+        "mix_enable" if parts.len() >= 3 => {
+            let mix_index = parts[1].parse().ok()?;
+            let source_index: u8 = parts[2].parse().ok()?;
+            Some(ProxyCommand::Mix { 
+                action: MixAction::Enable, 
+                mix_index, 
+                source: index_to_source(source_index)? 
+            })
+        }
         "callme_link" if parts.len() >= 3 => {
             let mix_index = parts[1].parse().ok()?;
             let callme_index: u8 = parts[2].parse().ok()?;
@@ -157,44 +290,3 @@ fn index_to_source(idx: u8) -> Option<Source> {
         _ => return None,
     })
 }
-
-impl ProxyCommand {
-    /// Build all payloads for this command (some commands need multiple packets)
-    pub fn build_payloads(&self, session_id: &[u8]) -> Vec<Vec<u8>> {
-        match self {
-            ProxyCommand::Mix { action, mix_index, source } => {
-                MixCommand::new(*action, *mix_index, *source).build_payloads(session_id)
-            }
-            _ => vec![self.build_payload(session_id)],
-        }
-    }
-}
-
-impl RodeCommand for ProxyCommand {
-    fn build_payload(&self, session_id: &[u8]) -> Vec<u8> {
-        match self {
-            ProxyCommand::Mute { fader_index, mute } => {
-                protocol::ChannelOutputMute { fader_index: *fader_index, mute: *mute }
-                    .build_payload(session_id)
-            }
-            ProxyCommand::Source { fader_index, source_id } => {
-                protocol::ChannelInputSource { fader_index: *fader_index, source_id: *source_id }
-                    .build_payload(session_id)
-            }
-            ProxyCommand::MicType { fader_index, mic_type } => {
-                protocol::InputMicrophoneType { fader_index: *fader_index, mic_type: *mic_type }
-                    .build_payload(session_id)
-            }
-            ProxyCommand::Level { fader_index, level } => {
-                protocol::FaderLevel { fader_index: *fader_index, level: *level }
-                    .build_payload(session_id)
-            }
-            ProxyCommand::Touch => {
-                protocol::ScreenTouched.build_payload(session_id)
-            }
-            ProxyCommand::Mix { action, mix_index, source } => {
-                MixCommand::new(*action, *mix_index, *source).build_payload(session_id)
-            }
-        }
-    }
-}
@@ -1,184 +1,602 @@
+mod handover;
 mod listener;
+mod reload;
 mod sniffer;
+mod state;
+mod supervisor;
 
 // Re-export from library
-pub use tcp_bridge::{names, protocol, commands};
+pub use tcp_bridge::{names, protocol, commands, routing};
 
 use clap::Parser;
 use std::net::SocketAddr;
-use tokio::io::{self, AsyncReadExt, AsyncWriteExt};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use futures::{SinkExt, StreamExt};
+use tokio::io::{self, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpStream};
+use tokio::signal::unix::{SignalKind, signal};
 use socket2::{Socket, Domain, Type};
-use tokio::sync::broadcast;
+use tokio::sync::{broadcast, watch};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::task::JoinSet;
+use tokio_util::codec::{FramedRead, FramedWrite};
 
+use handover::Connection;
+use protocol::PacketCodec;
+use state::ProxyState;
+use tcp_bridge::inject::ProxyCommand;
+use tcp_bridge::config::Config;
+use tcp_bridge::status::{HealthChange, SessionEvent};
+use tcp_bridge::traffic::Direction;
+
+/// Settings default to the config file (see `tcp_bridge::config`); flags override it
 #[derive(Parser, Debug)]
 #[command(author, version, about)]
 struct Args {
-    #[arg(long, default_value = "127.0.0.2")]
-    bind_ip: String,
+    /// Config file (default /etc/bridge.toml if it exists, or BRIDGE_CONFIG)
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Override a config setting, e.g. --setting pacing.packet_delay_ms=80
+    #[arg(long = "setting", value_name = "SECTION.KEY=VALUE")]
+    overrides: Vec<String>,
+
+    /// Print the effective configuration as TOML and exit
+    #[arg(long)]
+    print_config: bool,
+
+    #[arg(long)]
+    bind_ip: Option<String>,
+
+    #[arg(long)]
+    bind_port: Option<u16>,
+
+    #[arg(long)]
+    target_ip: Option<String>,
+
+    #[arg(long)]
+    target_port: Option<u16>,
+
+    #[arg(long)]
+    source_ip: Option<String>,
+
+    /// Maximum frame payload size; a larger frame closes the connection
+    #[arg(long)]
+    max_frame_size: Option<usize>,
+
+    /// Flag the mixer as unhealthy when a ping stays unanswered this long
+    #[arg(long)]
+    ping_timeout_ms: Option<u64>,
+
+    /// Inject with the default session ID (01 01 01 01) before one is sniffed
+    #[arg(long)]
+    allow_default_session: bool,
+
+    /// Re-inject the last command for each target after a session change
+    #[arg(long)]
+    reapply_on_session_change: bool,
+
+    /// JSON file with declarative command definitions
+    #[arg(long)]
+    spec: Option<PathBuf>,
+
+    /// Control socket path
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Add the loopback alias and DNAT rule, restart the GUI, and remove
+    /// them again when the proxy exits (needs root)
+    #[arg(long)]
+    setup_network: bool,
 
-    #[arg(long, default_value_t = 9000)]
-    bind_port: u16,
+    /// Print the commands --setup-network would run and exit
+    #[arg(long)]
+    print_setup: bool,
 
-    #[arg(long, default_value = "127.0.0.1")]
-    target_ip: String,
+    /// Take over the listening socket, live connections and session state
+    /// from the running bridge instead of binding (zero-downtime upgrade)
+    #[arg(long)]
+    handover: bool,
+}
 
-    #[arg(long, default_value_t = 2345)]
-    target_port: u16,
+impl Args {
+    /// The config file with this invocation's flags applied
+    fn config(&self) -> Result<Config, String> {
+        let mut config = Config::load(self.config.as_deref(), &self.overrides)?;
+        self.apply(&mut config);
+        Ok(config)
+    }
 
-    #[arg(long, default_value = "127.0.0.2")]
-    source_ip: String,
+    /// Flags win over the file, the environment and `--setting`
+    fn apply(&self, config: &mut Config) {
+        let proxy = &mut config.proxy;
+        if let Some(ip) = &self.bind_ip { proxy.bind_ip = ip.clone(); }
+        if let Some(port) = self.bind_port { proxy.bind_port = port; }
+        if let Some(ip) = &self.target_ip { proxy.target_ip = ip.clone(); }
+        if let Some(port) = self.target_port { proxy.target_port = port; }
+        if let Some(ip) = &self.source_ip { proxy.source_ip = ip.clone(); }
+        if let Some(size) = self.max_frame_size { proxy.max_frame_size = size; }
+        if let Some(ms) = self.ping_timeout_ms { proxy.ping_timeout_ms = ms; }
+        if let Some(spec) = &self.spec { proxy.spec = spec.clone(); }
+        if let Some(socket) = &self.socket { config.control.socket = socket.clone(); }
+        let features = &mut config.features;
+        features.allow_default_session |= self.allow_default_session;
+        features.reapply_on_session_change |= self.reapply_on_session_change;
+        features.setup_network |= self.setup_network;
+    }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = Args::parse();
+    // Shared with the reload, which applies the same flags again
+    let args = Arc::new(Args::parse());
+    let config = args.config()?;
+
+    if args.print_config {
+        print!("{}", config.to_toml());
+        return Ok(());
+    }
+    if args.print_setup {
+        supervisor::print_setup(&config.proxy.network_setup()?);
+        return Ok(());
+    }
+    if config.features.setup_network && std::env::var_os(supervisor::SUPERVISED_ENV).is_none() {
+        let code = supervisor::supervise(config.proxy.network_setup()?, args.handover).await?;
+        std::process::exit(code);
+    }
+    let proxy_config = &config.proxy;
+
+    // Command Specs (optional - built-in commands work without them)
+    let specs = match protocol::CommandSpecs::load(&proxy_config.spec) {
+        Ok(specs) => {
+            println!("Loaded {} command specs from {}", specs.len(), proxy_config.spec.display());
+            specs
+        }
+        Err(e) => {
+            println!("No command specs loaded: {}", e);
+            protocol::CommandSpecs::default()
+        }
+    };
+    let state = ProxyState::shared(config.clone(), specs);
+    let load: reload::Loader = {
+        let args = args.clone();
+        Arc::new(move || args.config())
+    };
+    {
+        let mut hangup = signal(SignalKind::hangup())?;
+        let load = load.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                println!("[Config] SIGHUP, reloading");
+                reload::reload(&load, &state);
+            }
+        });
+    }
 
     // Command Channel
     let (cmd_tx, _cmd_rx) = broadcast::channel(16);
+
+    // Session Change Events
+    let (session_tx, _session_rx) = broadcast::channel::<SessionEvent>(16);
+    {
+        let mut session_rx = session_tx.subscribe();
+        let cmd_tx = cmd_tx.clone();
+        let state = state.clone();
+        tokio::spawn(async move {
+            while let Ok(event) = session_rx.recv().await {
+                on_session_event(&event, &state, &cmd_tx);
+            }
+        });
+    }
     
+    // Take over before binding the control socket, so commands keep
+    // going to the old bridge until it has parked its connections
+    let (listener, taken_over) = if args.handover {
+        let taken_over = handover::take_over(&config.control.handover_socket)?;
+        state.lock().unwrap().restore(&taken_over.state);
+        // From here on the old bridge exits and the sockets are ours
+        taken_over.confirmation.send()?;
+        supervisor::notify_taken_over();
+        println!("[Handover] Took over {} connection(s) from the running bridge", taken_over.connections.len());
+        (taken_over.listener, taken_over.connections)
+    } else {
+        let bind_addr: SocketAddr = format!("{}:{}", proxy_config.bind_ip, proxy_config.bind_port).parse()?;
+        (TcpListener::bind(bind_addr).await?, Vec::new())
+    };
+    println!("Proxy listening on {}", listener.local_addr()?);
+    println!("Targeting {}:{} (Binding Source: {})", proxy_config.target_ip, proxy_config.target_port, proxy_config.source_ip);
+
     // Start Listener
     {
         let tx = cmd_tx.clone();
+        let state = state.clone();
+        let control = config.control.clone();
+        let load = load.clone();
         tokio::spawn(async move {
-            listener::start_listener(tx).await;
+            listener::start_listener(tx, state, control, load).await;
         });
     }
 
-    let bind_addr: SocketAddr = format!("{}:{}", args.bind_ip, args.bind_port).parse()?;
-    let listener = TcpListener::bind(bind_addr).await?;
-    println!("Proxy listening on {}", bind_addr);
-    println!("Targeting {}:{} (Binding Source: {})", args.target_ip, args.target_port, args.source_ip);
+    let (mut handover_signal, handover_rx) = watch::channel(false);
+    let mut handover_requests = handover::listen(&config.control.handover_socket)?;
+    let mut proxy = Proxy {
+        state: state.clone(),
+        handover: handover_rx,
+        session_tx,
+        max_frame_size: proxy_config.max_frame_size,
+    };
+    let mut connections = JoinSet::new();
+    let session_id = state.lock().unwrap().session_id.clone();
+    for conn in taken_over {
+        connections.spawn(proxy.clone().forward(conn, cmd_tx.subscribe(), session_id.clone()));
+    }
 
-    while let Ok((mut client_socket, addr)) = listener.accept().await {
-        println!("New connection from: {}", addr);
-        
-        let target_ip = args.target_ip.clone();
-        let target_port = args.target_port;
-        let source_ip = args.source_ip.clone();
-        
-        // Each connection gets a receiver
-        let mut cmd_rx = cmd_tx.subscribe();
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let Ok((client_socket, addr)) = accepted else { break };
+                println!("New connection from: {}", addr);
 
-        tokio::spawn(async move {
-            match connect_to_target(&target_ip, target_port, &source_ip).await {
-                Ok(mut server_socket) => {
-                    let (mut client_reader, mut client_writer) = client_socket.split();
-                    let (mut server_reader, mut server_writer) = server_socket.split();
-                    
-                    // Internal channel for S->C Injection (Loopback)
-                    let (inject_tx, mut inject_rx) = tokio::sync::mpsc::channel::<Vec<u8>>(16);
-
-                    // Client -> Server (PLUS Injection)
-                    let client_to_server = async {
-                        let mut buf = [0u8; 4096];
-                        let mut sniffer = sniffer::SnifferState::new();
-                        let mut current_session_id = vec![0x01, 0x01, 0x01, 0x01]; // Default
-                        
-                        // We need to move inject_tx into this block
-                        let inject_tx = inject_tx; 
-
-                        loop {
-                            tokio::select! {
-                                res = client_reader.read(&mut buf) => {
-                                    match res {
-                                        Ok(0) => break, // EOF
-                                        Ok(n) => {
-                                            sniffer.handle_packet("C->S", &buf[..n]);
-                                            
-                                            // Dynamic Session ID Sniffing
-                                            if let Some(sid) = protocol::extract_session_id(&buf[..n]) {
-                                                 current_session_id = sid;
-                                                 // println!("[Proxy] Sniffed SessionID: {:02x?}", current_session_id);
-                                            }
-
-                                            if let Err(e) = server_writer.write_all(&buf[..n]).await {
-                                                eprintln!("Failed to write to server: {}", e);
-                                                break;
-                                            }
-                                        }
-                                        Err(e) => {
-                                            eprintln!("Client read error: {}", e);
-                                            break;
-                                        }
+                let target_ip = proxy_config.target_ip.clone();
+                let target_port = proxy_config.target_port;
+                let source_ip = proxy_config.source_ip.clone();
+                let proxy = proxy.clone();
+
+                // Each connection gets a receiver
+                let cmd_rx = cmd_tx.subscribe();
+
+                connections.spawn(async move {
+                    match connect_to_target(&target_ip, target_port, &source_ip).await {
+                        Ok(server_socket) => proxy.forward(Connection::new(client_socket, server_socket), cmd_rx, None).await,
+                        Err(e) => {
+                            eprintln!("Failed to connect to target: {}", e);
+                            None
+                        }
+                    }
+                });
+            }
+            // Reap closed connections; one that parked after an aborted
+            // handover resumes
+            Some(result) = connections.join_next(), if !connections.is_empty() => {
+                if let Ok(Some(conn)) = result {
+                    let session_id = state.lock().unwrap().session_id.clone();
+                    connections.spawn(proxy.clone().forward(conn, cmd_tx.subscribe(), session_id));
+                }
+            }
+            Some(peer) = handover_requests.recv() => {
+                println!("[Handover] New bridge taking over, parking {} connection(s)", connections.len());
+                handover_signal.send_replace(true);
+                let mut parked = Vec::new();
+                let parking = tokio::time::timeout(handover::PARK_TIMEOUT, async {
+                    while let Some(result) = connections.join_next().await {
+                        if let Ok(Some(conn)) = result {
+                            parked.push(conn);
+                        }
+                    }
+                }).await;
+
+                let handed_over = match parking {
+                    Ok(()) => {
+                        let session = state.lock().unwrap().session_state();
+                        tokio::task::block_in_place(|| handover::hand_over(peer, &listener, &parked, session))
+                    }
+                    // Dropping the peer aborts the new bridge's take-over
+                    Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, format!(
+                        "{} connection(s) not parked within {} s", connections.len(), handover::PARK_TIMEOUT.as_secs()
+                    ))),
+                };
+                match handed_over {
+                    Ok(()) => {
+                        println!("[Handover] Handed over {} connection(s), exiting", parked.len());
+                        std::process::exit(handover::EXIT_HANDED_OVER);
+                    }
+                    Err(e) => {
+                        eprintln!("[Handover] Failed: {} - resuming", e);
+                        // Connections still parking keep the old signal and
+                        // resume once they are reaped
+                        let (signal, handover_rx) = watch::channel(false);
+                        handover_signal = signal;
+                        proxy.handover = handover_rx;
+                        let session_id = state.lock().unwrap().session_id.clone();
+                        for conn in parked {
+                            connections.spawn(proxy.clone().forward(conn, cmd_tx.subscribe(), session_id.clone()));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// What each connection task shares with the rest of the proxy
+#[derive(Clone)]
+struct Proxy {
+    state: state::SharedState,
+    session_tx: broadcast::Sender<SessionEvent>,
+    /// Set when a new bridge takes over; connections park between frames
+    handover: watch::Receiver<bool>,
+    max_frame_size: usize,
+}
+
+impl Proxy {
+    /// Forward frames both ways (plus injection) until either side closes.
+    /// Returns the connection if it was parked for a handover instead.
+    async fn forward(
+        self,
+        mut conn: Connection,
+        mut cmd_rx: broadcast::Receiver<ProxyCommand>,
+        session_id: Option<Vec<u8>>,
+    ) -> Option<Connection> {
+        let Proxy { state, session_tx, handover, max_frame_size } = self;
+        state.lock().unwrap().connection_opened();
+        let (client_buffered, server_buffered) = {
+            let (client_reader, client_writer) = conn.client.split();
+            let (server_reader, server_writer) = conn.server.split();
+            let mut client_reader = FramedRead::new(client_reader, PacketCodec::new(max_frame_size));
+            let mut client_writer = FramedWrite::new(client_writer, PacketCodec::new(max_frame_size));
+            let mut server_reader = FramedRead::new(server_reader, PacketCodec::new(max_frame_size));
+            let mut server_writer = FramedWrite::new(server_writer, PacketCodec::new(max_frame_size));
+            // Partly read frames from before a handover
+            client_reader.read_buffer_mut().extend_from_slice(&conn.client_buffered);
+            server_reader.read_buffer_mut().extend_from_slice(&conn.server_buffered);
+
+            // Internal channel for S->C Injection (Loopback)
+            let (inject_tx, mut inject_rx) = tokio::sync::mpsc::channel::<protocol::Packet>(16);
+
+            // Client -> Server (PLUS Injection)
+            let client_to_server = async {
+                let mut sniffer = sniffer::SnifferState::new();
+                let mut current_session_id: Option<Vec<u8>> = session_id;
+
+                // We need to move inject_tx into this block
+                let inject_tx = inject_tx;
+                let mut handover = handover.clone();
+                let mut health_check = tokio::time::interval(Duration::from_secs(1));
+
+                let parked = loop {
+                    tokio::select! {
+                        res = client_reader.next() => {
+                            match res {
+                                None => break None, // EOF
+                                Some(Ok(packet)) => {
+                                    log_discarded("C->S", client_reader.decoder_mut());
+                                    let bytes = packet.to_bytes();
+                                    let config = state.lock().unwrap().config.clone();
+                                    sniffer.handle_packet("C->S", &bytes, &config.logging);
+
+                                    observe_callme(&state, &packet.payload);
+                                    // The GUI's own mixDisabled writes are not echoed back
+                                    state.lock().unwrap().observe(Direction::ToMixer, &packet.payload);
+                                    if protocol::is_ping(&packet.payload) {
+                                        state.lock().unwrap().ping.on_ping(Instant::now());
+                                    } else if let Some(sid) = protocol::extract_session_id(&bytes) {
+                                         // Dynamic Session ID Sniffing
+                                         let event = state.lock().unwrap().observe_session(&sid);
+                                         current_session_id = Some(sid);
+                                         if let Some(event) = event {
+                                             let _ = session_tx.send(event);
+                                         }
                                     }
-                                }
-                                Ok(cmd) = cmd_rx.recv() => {
-                                    // Inject Command(s)!
-                                    println!("[Proxy] Injecting Command: {:?}", cmd);
-                                    let payloads = cmd.build_payloads(&current_session_id);
-                                    
-                                    for (i, payload) in payloads.iter().enumerate() {
-                                        // Rate limit: delay between multi-packet commands
-                                        if i > 0 {
-                                            tokio::time::sleep(tokio::time::Duration::from_millis(50)).await;
-                                        }
-                                        
-                                        // UI SYNC: Loopback injection
-                                        let packet = protocol::Packet::new(payload.clone());
-                                        let bytes = packet.to_bytes();
-                                        if let Err(e) = inject_tx.send(bytes.clone()).await {
-                                             eprintln!("Failed to queue UI Sync: {}", e);
-                                        }
-                                        
-                                        sniffer::print_hexdump("INJECTED", &bytes);
-                                        
-                                        if let Err(e) = server_writer.write_all(&bytes).await {
-                                            eprintln!("Failed to inject command: {}", e);
-                                        }
+
+                                    if let Err(e) = server_writer.send(packet).await {
+                                        eprintln!("Failed to write to server: {}", e);
+                                        break None;
                                     }
-                                    println!("[Proxy] Injection Sent ({} packets).", payloads.len());
+                                }
+                                Some(Err(e)) => {
+                                    eprintln!("Client read error: {}", e);
+                                    break None;
                                 }
                             }
                         }
-                        // Shutdown
-                        let _ = server_writer.shutdown().await;
-                    };
-
-                    // Server -> Client
-                    let server_to_client = async {
-                        let mut buf = [0u8; 4096];
-                        let mut sniffer = sniffer::SnifferState::new();
-                        loop {
-                            tokio::select! {
-                                res = server_reader.read(&mut buf) => {
-                                    match res {
-                                        Ok(0) => break, // EOF
-                                        Ok(n) => {
-                                            sniffer.handle_packet("S->C", &buf[..n]);
-                                            if let Err(e) = client_writer.write_all(&buf[..n]).await {
-                                                eprintln!("Failed to write to client: {}", e);
-                                                break;
-                                            }
-                                        }
-                                        Err(e) => {
-                                            eprintln!("Server read error: {}", e);
-                                            break;
+                        received = cmd_rx.recv() => {
+                            let cmd = match received {
+                                Ok(cmd) => cmd,
+                                Err(RecvError::Lagged(missed)) => {
+                                    eprintln!("[Proxy] Command queue overflowed - {} commands not injected", missed);
+                                    continue;
+                                }
+                                Err(RecvError::Closed) => continue,
+                            };
+                            let (callme, config) = {
+                                let state = state.lock().unwrap();
+                                (state.callme_channel(), state.config.clone())
+                            };
+                            // Read per command, so a reload enabling the default session applies
+                            let session_id = match current_session_id.as_deref() {
+                                Some(sid) => sid,
+                                None if config.features.allow_default_session => &protocol::DEFAULT_SESSION_ID,
+                                None => {
+                                    eprintln!("[Proxy] No session ID sniffed yet - not injecting {:?}", cmd);
+                                    continue;
+                                }
+                            };
+
+                            // Inject Command(s)!
+                            println!("[Proxy] Injecting Command: {:?}", cmd);
+                            let payloads = match cmd.build_payloads(session_id, callme.as_ref()) {
+                                Ok(payloads) => payloads,
+                                Err(e) => {
+                                    eprintln!("[Proxy] Not injecting {:?}: {}", cmd, e);
+                                    continue;
+                                }
+                            };
+
+                            for (i, payload) in payloads.iter().enumerate() {
+                                // Rate limit: delay between multi-packet commands
+                                if i > 0 {
+                                    tokio::time::sleep(Duration::from_millis(config.pacing.packet_delay_ms)).await;
+                                }
+
+                                // UI SYNC: Loopback injection
+                                let packet = protocol::Packet::new(payload.clone());
+                                if let Err(e) = inject_tx.send(packet.clone()).await {
+                                     eprintln!("Failed to queue UI Sync: {}", e);
+                                }
+
+                                sniffer.dump("INJECTED", &packet.to_bytes(), &config.logging);
+
+                                if let Err(e) = server_writer.send(packet).await {
+                                    eprintln!("Failed to inject command: {}", e);
+                                }
+                            }
+                            println!("[Proxy] Injection Sent ({} packets).", payloads.len());
+                            state.lock().unwrap().record_injected(&cmd);
+                        }
+                        _ = health_check.tick() => {
+                            let change = state.lock().unwrap().ping.check(Instant::now());
+                            if let Some(change) = change {
+                                log_health(change);
+                            }
+                        }
+                        Ok(()) = handover.changed() => break Some(client_reader.read_buffer().to_vec()),
+                    }
+                };
+                // Shutdown, unless the socket goes to the new bridge
+                if parked.is_none() {
+                    let _ = server_writer.into_inner().shutdown().await;
+                }
+                parked
+            };
+
+            // Server -> Client
+            let server_to_client = async {
+                let mut sniffer = sniffer::SnifferState::new();
+                let mut handover = handover.clone();
+                let parked = loop {
+                    tokio::select! {
+                        res = server_reader.next() => {
+                            match res {
+                                None => break None, // EOF
+                                Some(Ok(packet)) => {
+                                    log_discarded("S->C", server_reader.decoder_mut());
+                                    let config = state.lock().unwrap().config.clone();
+                                    sniffer.handle_packet("S->C", &packet.to_bytes(), &config.logging);
+                                    observe_callme(&state, &packet.payload);
+                                    state.lock().unwrap().observe(Direction::FromMixer, &packet.payload);
+                                    if protocol::is_ping(&packet.payload) {
+                                        let change = state.lock().unwrap().ping.on_reply(Instant::now());
+                                        if let Some(change) = change {
+                                            log_health(change);
                                         }
                                     }
-                                }
-                                Some(injected_bytes) = inject_rx.recv() => {
-                                    // Handle Loopback Injection
-                                    sniffer::print_hexdump("INJECTED_LOOPBACK", &injected_bytes);
-                                    if let Err(e) = client_writer.write_all(&injected_bytes).await {
-                                        eprintln!("Failed to write loopback to client: {}", e);
-                                        break;
+                                    if let Err(e) = client_writer.send(packet).await {
+                                        eprintln!("Failed to write to client: {}", e);
+                                        break None;
                                     }
                                 }
+                                Some(Err(e)) => {
+                                    eprintln!("Server read error: {}", e);
+                                    break None;
+                                }
                             }
                         }
-                        // Shutdown
-                        let _ = client_writer.shutdown().await;
-                    };
-
-                    tokio::join!(client_to_server, server_to_client);
+                        Some(injected) = inject_rx.recv() => {
+                            // Handle Loopback Injection
+                            let config = state.lock().unwrap().config.clone();
+                            sniffer.dump("INJECTED_LOOPBACK", &injected.to_bytes(), &config.logging);
+                            if let Err(e) = client_writer.send(injected).await {
+                                eprintln!("Failed to write loopback to client: {}", e);
+                                break None;
+                            }
+                        }
+                        Ok(()) = handover.changed() => break Some(server_reader.read_buffer().to_vec()),
+                    }
+                };
+                if parked.is_none() {
+                    // Shutdown
+                    let _ = client_writer.into_inner().shutdown().await;
+                    return None;
                 }
-                Err(e) => eprintln!("Failed to connect to target: {}", e),
-            }
-        });
+                // Deliver loopback frames still queued until C->S has parked too
+                while let Some(injected) = inject_rx.recv().await {
+                    if client_writer.send(injected).await.is_err() {
+                        return None;
+                    }
+                }
+                parked
+            };
+
+            tokio::join!(client_to_server, server_to_client)
+        };
+        state.lock().unwrap().connection_closed();
+
+        // Only a connection parked in both directions can be handed over
+        Some(Connection {
+            client_buffered: client_buffered?,
+            server_buffered: server_buffered?,
+            ..conn
+        })
     }
+}
 
-    Ok(())
+/// Learn the CallMe channel's session ID and prefix layout from CallMe mix frames
+fn observe_callme(state: &state::SharedState, payload: &[u8]) {
+    if protocol::is_unexpected_callme_prefix(payload) {
+        eprintln!("[CallMe] Unexpected CallMe prefix in {} - mix prefix layout may have changed", hex::encode(payload));
+    }
+    let mut state = state.lock().unwrap();
+    if let Some(sid) = protocol::extract_callme_session(payload)
+        && state.observe_callme_session(sid)
+    {
+        println!("[CallMe] Learned CallMe session ID {}", hex::encode(sid));
+    }
+    if state.callme_layout.observe(payload) {
+        match state.callme_layout.prefix_base() {
+            Some(base) => println!("[CallMe] Using CallMe prefix base {}", base),
+            None => eprintln!("[CallMe] CallMe prefixes do not fit one layout - CallMe mix commands refused"),
+        }
+    }
+}
+
+/// Session change hook: log it and optionally re-apply overridden state
+fn on_session_event(
+    event: &SessionEvent,
+    state: &state::SharedState,
+    cmd_tx: &broadcast::Sender<ProxyCommand>,
+) {
+    let Some(old) = &event.old else {
+        println!("[Session] Sniffed session ID {}", event.new);
+        return;
+    };
+    println!("[Session] Session ID changed: {} -> {}", old, event.new);
+
+    let reapply = state.lock().unwrap().config.features.reapply_on_session_change;
+    if reapply {
+        let overrides = state.lock().unwrap().overrides.clone();
+        if overrides.is_empty() {
+            return;
+        }
+        println!("[Session] Re-applying {} overridden targets", overrides.len());
+        // One batch, so a long override list can't overflow the command queue
+        let cmds = overrides.into_iter().map(|(_, cmd)| cmd).collect();
+        let _ = cmd_tx.send(ProxyCommand::Batch(cmds));
+    }
+}
+
+/// Garbage the codec skipped before this frame is not forwarded
+fn log_discarded(direction: &str, codec: &mut PacketCodec) {
+    let discarded = codec.take_discarded();
+    if discarded > 0 {
+        eprintln!("[Proxy] {}: dropped {} bytes of garbage before a frame header", direction, discarded);
+    }
+}
+
+fn log_health(change: HealthChange) {
+    match change {
+        HealthChange::Unhealthy { waiting } => eprintln!(
+            "[Health] Mixer stopped answering pings (no reply for {:.1}s) - audio engine may be hung",
+            waiting.as_secs_f32()
+        ),
+        HealthChange::Recovered { latency } => println!(
+            "[Health] Mixer answering pings again (latency {} ms)", latency.as_millis()
+        ),
+    }
 }
 
 async fn connect_to_target(ip: &str, port: u16, source_ip: &str) -> io::Result<TcpStream> {
@@ -195,3 +613,24 @@ async fn connect_to_target(ip: &str, port: u16, source_ip: &str) -> io::Result<T
     TcpStream::from_std(std_stream)
 }
 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flags_override_the_config() {
+        let mut config = Config::default();
+        config.proxy.bind_port = 9001;
+        config.proxy.target_port = 9002;
+        config.features.allow_default_session = true;
+
+        let args = Args::parse_from(["tcp-bridge", "--setting", "proxy.bind_port=9003", "--bind-port", "9004", "--spec", "/etc/specs.json"]);
+        args.apply(&mut config);
+        assert_eq!(config.proxy.bind_port, 9004);
+        assert_eq!(config.proxy.spec, PathBuf::from("/etc/specs.json"));
+        // Settings without a flag, and switches that are off, are left alone
+        assert_eq!(config.proxy.target_port, 9002);
+        assert!(config.features.allow_default_session);
+    }
+}
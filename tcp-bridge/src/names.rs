@@ -10,7 +10,7 @@ use std::fmt;
 use std::str::FromStr;
 
 /// Mix output bus (where audio goes TO)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum MixOutput {
     Headphone1, Headphone2, Headphone3, Headphone4,
@@ -20,6 +20,28 @@ pub enum MixOutput {
 }
 
 impl MixOutput {
+    pub const ALL: [MixOutput; 13] = [
+        Self::Headphone1, Self::Headphone2, Self::Headphone3, Self::Headphone4,
+        Self::Speaker, Self::Recording, Self::Bluetooth,
+        Self::Usb1, Self::Chat, Self::Usb2,
+        Self::CallMe1, Self::CallMe2, Self::CallMe3,
+    ];
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|m| m.to_index() == index)
+    }
+
+    /// Shortest alias, for table headers
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            Self::Headphone1 => "hp1", Self::Headphone2 => "hp2",
+            Self::Headphone3 => "hp3", Self::Headphone4 => "hp4",
+            Self::Speaker => "spk", Self::Recording => "rec", Self::Bluetooth => "bt",
+            Self::Usb1 => "usb1", Self::Chat => "chat", Self::Usb2 => "usb2",
+            Self::CallMe1 => "cm1", Self::CallMe2 => "cm2", Self::CallMe3 => "cm3",
+        }
+    }
+
     pub fn to_index(&self) -> u8 {
         match self {
             Self::Headphone1 => 10, Self::Headphone2 => 11,
@@ -67,7 +89,7 @@ impl fmt::Display for MixOutput {
 }
 
 /// Audio source (where audio comes FROM)
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
 #[serde(rename_all = "lowercase")]
 pub enum Source {
     Combo1, Combo2, Combo3, Combo4,
@@ -78,6 +100,14 @@ pub enum Source {
 }
 
 impl Source {
+    pub const ALL: [Source; 19] = [
+        Self::Combo1, Self::Combo2, Self::Combo3, Self::Combo4,
+        Self::Combo1_2, Self::Combo2_3, Self::Combo3_4,
+        Self::Usb1, Self::Chat, Self::Usb2, Self::Bluetooth, Self::SoundPad,
+        Self::VirtualGame, Self::VirtualMusic, Self::VirtualA, Self::VirtualB,
+        Self::CallMe1, Self::CallMe2, Self::CallMe3,
+    ];
+
     pub fn to_index(&self) -> u8 {
         match self {
             Self::Combo1 => 4, Self::Combo2 => 5, Self::Combo3 => 6, Self::Combo4 => 7,
@@ -89,6 +119,10 @@ impl Source {
         }
     }
 
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.into_iter().find(|s| s.to_index() == index)
+    }
+
     /// CallMe sources require special protocol encoding
     pub fn is_callme(&self) -> bool {
         matches!(self, Self::CallMe1 | Self::CallMe2 | Self::CallMe3)
@@ -147,6 +181,12 @@ pub enum Fader {
 }
 
 impl Fader {
+    pub const ALL: [Fader; 9] = [
+        Self::Physical1, Self::Physical2, Self::Physical3,
+        Self::Physical4, Self::Physical5, Self::Physical6,
+        Self::Virtual1, Self::Virtual2, Self::Virtual3,
+    ];
+
     pub fn to_index(&self) -> u8 {
         match self {
             Self::Physical1 => 0, Self::Physical2 => 1, Self::Physical3 => 2,
@@ -154,6 +194,19 @@ impl Fader {
             Self::Virtual1 => 6, Self::Virtual2 => 7, Self::Virtual3 => 8,
         }
     }
+
+    pub fn from_index(index: u8) -> Option<Self> {
+        Self::ALL.get(index as usize).copied()
+    }
+
+    /// Shortest alias, for table headers
+    pub fn short_name(&self) -> &'static str {
+        match self {
+            Self::Physical1 => "p1", Self::Physical2 => "p2", Self::Physical3 => "p3",
+            Self::Physical4 => "p4", Self::Physical5 => "p5", Self::Physical6 => "p6",
+            Self::Virtual1 => "v1", Self::Virtual2 => "v2", Self::Virtual3 => "v3",
+        }
+    }
 }
 
 impl FromStr for Fader {
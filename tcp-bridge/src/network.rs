@@ -0,0 +1,119 @@
+//! Loopback alias and iptables DNAT rule that send the GUI through the
+//! proxy, and the Linux lookups to inspect them. Used by `tcp-bridge
+//! --setup-network` and `bridge-ctl doctor`.
+
+use std::net::Ipv4Addr;
+use std::process::Command;
+
+/// Process that is restarted so it reconnects through the proxy
+pub const GUI_PROCESS: &str = "rc_gui_juce";
+
+/// Addresses of one proxy deployment (defaults as in `run-proxy.sh`)
+#[derive(Debug, Clone)]
+pub struct NetworkSetup {
+    pub bind_ip: Ipv4Addr,
+    pub bind_port: u16,
+    pub target_ip: Ipv4Addr,
+    pub target_port: u16,
+    /// The proxy's own upstream connection comes from here and skips the rule
+    pub source_ip: Ipv4Addr,
+}
+
+impl NetworkSetup {
+    /// `iptables -t nat <action> OUTPUT <rule>`, action `-A`, `-D` or `-C`
+    pub fn iptables(&self, action: &str) -> Vec<String> {
+        let rule = format!(
+            "-t nat {} OUTPUT -p tcp -d {} --dport {} ! -s {} -j DNAT --to-destination {}:{}",
+            action, self.target_ip, self.target_port, self.source_ip, self.bind_ip, self.bind_port
+        );
+        std::iter::once("iptables").chain(rule.split(' ')).map(str::to_string).collect()
+    }
+
+    /// Addresses that need an alias on `lo` (127.0.0.1 always exists)
+    pub fn aliases(&self) -> Vec<Ipv4Addr> {
+        let mut ips = vec![self.bind_ip, self.source_ip];
+        ips.dedup();
+        ips.retain(|ip| *ip != Ipv4Addr::LOCALHOST);
+        ips
+    }
+
+    /// Number of copies of the DNAT rule in the nat OUTPUT chain
+    pub fn count_rules(&self) -> Result<usize, String> {
+        let output = run(&["iptables", "-t", "nat", "-S", "OUTPUT"])?;
+        let destination = format!("--to-destination {}:{}", self.bind_ip, self.bind_port);
+        let dport = format!("--dport {}", self.target_port);
+        Ok(output.lines()
+            .filter(|l| l.contains("-j DNAT") && l.contains(&destination) && l.contains(&dport))
+            .count())
+    }
+}
+
+/// `ip addr <action> <ip>/8 dev lo`, action `add` or `del`
+pub fn alias_command(action: &str, ip: Ipv4Addr) -> Vec<String> {
+    ["ip", "addr", action, &format!("{}/8", ip), "dev", "lo"].map(str::to_string).to_vec()
+}
+
+/// Whether `ip` is configured on `lo`
+pub fn has_alias(ip: Ipv4Addr) -> Result<bool, String> {
+    let output = run(&["ip", "-o", "-4", "addr", "show", "dev", "lo"])?;
+    Ok(output.contains(&format!(" {}/", ip)))
+}
+
+/// A command as it would be typed in a shell
+pub fn shell(command: &[String]) -> String {
+    command.iter()
+        .map(|arg| if arg == "!" { "'!'".to_string() } else { arg.clone() })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Run a system tool and return its stdout
+pub fn run<S: AsRef<str>>(command: &[S]) -> Result<String, String> {
+    let (program, args) = command.split_first().ok_or("empty command")?;
+    let program = program.as_ref();
+    let output = Command::new(program).args(args.iter().map(AsRef::as_ref)).output()
+        .map_err(|e| format!("cannot run {}: {}", program, e))?;
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        let reason = if stderr.trim().is_empty() { output.status.to_string() } else { stderr.trim().to_string() };
+        return Err(format!("{} failed: {}", program, reason));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// PIDs of processes whose name is `name`
+pub fn pids_of(name: &str) -> Result<Vec<u32>, String> {
+    let entries = std::fs::read_dir("/proc").map_err(|e| format!("cannot read /proc: {}", e))?;
+    let mut pids: Vec<u32> = entries.filter_map(|entry| {
+        let pid: u32 = entry.ok()?.file_name().to_str()?.parse().ok()?;
+        let comm = std::fs::read_to_string(format!("/proc/{}/comm", pid)).ok()?;
+        (comm.trim() == name).then_some(pid)
+    }).collect();
+    pids.sort();
+    Ok(pids)
+}
+
+/// Parent PID of a process (from `/proc/<pid>/stat`)
+pub fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = std::fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The name in parentheses may contain spaces; state and ppid follow it
+    stat.rsplit_once(')')?.1.split_whitespace().nth(1)?.parse().ok()
+}
+
+/// Whether a TCP socket listens on `ip:port` (from `/proc/net/tcp`)
+pub fn is_listening(ip: Ipv4Addr, port: u16) -> Result<bool, String> {
+    const TCP_LISTEN: u8 = 0x0A;
+    let table = std::fs::read_to_string("/proc/net/tcp").map_err(|e| format!("cannot read /proc/net/tcp: {}", e))?;
+    Ok(table.lines().skip(1).any(|line| {
+        let parse = || {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let (local_ip, local_port) = fields.get(1)?.split_once(':')?;
+            // Address is the raw little-endian u32
+            let local_ip = Ipv4Addr::from(u32::from_str_radix(local_ip, 16).ok()?.swap_bytes());
+            let local_port = u16::from_str_radix(local_port, 16).ok()?;
+            let state = u8::from_str_radix(fields.get(3)?, 16).ok()?;
+            Some(state == TCP_LISTEN && (local_ip == ip || local_ip.is_unspecified()) && local_port == port)
+        };
+        parse().unwrap_or(false)
+    }))
+}
@@ -0,0 +1,186 @@
+//! Tokio codec for Rodecaster frames.
+//!
+//! Frame layout: `[Magic: 4B LE] [Length: 4B LE] [Payload]`. The decoder
+//! handles partial reads and skips garbage until the next magic header
+//! (see `take_discarded`). A length above the maximum frame size is an
+//! error, as in the encoder: rescanning inside that payload could lock on
+//! to a false magic.
+
+use bytes::{Buf, BufMut, BytesMut};
+use std::io;
+use tokio_util::codec::{Decoder, Encoder};
+
+use super::Packet;
+
+/// Magic header bytes as they appear on the wire
+const MAGIC_BYTES: [u8; 4] = Packet::MAGIC.to_le_bytes();
+const HEADER_LEN: usize = 8;
+
+/// Default maximum payload size accepted by the decoder
+pub const DEFAULT_MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct PacketCodec {
+    max_frame_size: usize,
+    discarded: usize,
+}
+
+impl PacketCodec {
+    pub fn new(max_frame_size: usize) -> Self {
+        PacketCodec { max_frame_size, discarded: 0 }
+    }
+
+    /// Garbage bytes skipped while resynchronizing since the last call;
+    /// callers log them, as they are not forwarded
+    pub fn take_discarded(&mut self) -> usize {
+        std::mem::take(&mut self.discarded)
+    }
+
+    /// Drop everything before the next magic header.
+    /// Keeps a possible partial magic at the end of the buffer.
+    fn resync(&mut self, src: &mut BytesMut) {
+        let skip = match src.windows(MAGIC_BYTES.len()).position(|w| w == MAGIC_BYTES) {
+            Some(pos) => pos,
+            None => src.len().saturating_sub(MAGIC_BYTES.len() - 1),
+        };
+        if skip > 0 {
+            src.advance(skip);
+            self.discarded += skip;
+        }
+    }
+}
+
+impl Default for PacketCodec {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_FRAME_SIZE)
+    }
+}
+
+impl Decoder for PacketCodec {
+    type Item = Packet;
+    type Error = io::Error;
+
+    fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Packet>, io::Error> {
+        self.resync(src);
+        if src.len() < HEADER_LEN {
+            return Ok(None);
+        }
+
+        let length = u32::from_le_bytes([src[4], src[5], src[6], src[7]]);
+        if length as usize > self.max_frame_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Frame of {} bytes exceeds maximum {}", length, self.max_frame_size),
+            ));
+        }
+
+        let frame_len = HEADER_LEN + length as usize;
+        if src.len() < frame_len {
+            src.reserve(frame_len - src.len());
+            return Ok(None);
+        }
+
+        src.advance(HEADER_LEN);
+        let payload = src.split_to(length as usize).to_vec();
+        Ok(Some(Packet { header: Packet::MAGIC, length, payload }))
+    }
+}
+
+impl Encoder<Packet> for PacketCodec {
+    type Error = io::Error;
+
+    fn encode(&mut self, packet: Packet, dst: &mut BytesMut) -> Result<(), io::Error> {
+        if packet.payload.len() > self.max_frame_size {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("Frame of {} bytes exceeds maximum {}", packet.payload.len(), self.max_frame_size),
+            ));
+        }
+        dst.reserve(HEADER_LEN + packet.payload.len());
+        dst.put_u32_le(packet.header);
+        dst.put_u32_le(packet.length);
+        dst.put_slice(&packet.payload);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(payload: &[u8]) -> Vec<u8> {
+        Packet::new(payload.to_vec()).to_bytes()
+    }
+
+    #[test]
+    fn round_trip() {
+        let mut codec = PacketCodec::default();
+        let mut buf = BytesMut::new();
+        codec.encode(Packet::new(b"ping".to_vec()), &mut buf).unwrap();
+        assert_eq!(&buf[..], &frame(b"ping")[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap().payload, b"ping");
+        assert!(buf.is_empty());
+    }
+
+    #[test]
+    fn split_reads() {
+        let bytes = [frame(b"first"), frame(b"second")].concat();
+        let mut codec = PacketCodec::default();
+        let mut buf = BytesMut::new();
+        let mut decoded = Vec::new();
+        // One byte at a time, splitting magic, length and payload
+        for &byte in &bytes {
+            buf.put_u8(byte);
+            while let Some(packet) = codec.decode(&mut buf).unwrap() {
+                decoded.push(packet.payload);
+            }
+        }
+        assert_eq!(decoded, [b"first".to_vec(), b"second".to_vec()]);
+        assert_eq!(codec.take_discarded(), 0);
+    }
+
+    #[test]
+    fn leading_garbage() {
+        let mut codec = PacketCodec::default();
+        // The last 3 bytes could be the start of a magic split across reads
+        let mut buf = BytesMut::from(&[0x00, 0x11, 0x22, 0x2c, 0x9e][..]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        assert_eq!(&buf[..], &[0x22, 0x2c, 0x9e]);
+        assert_eq!(codec.take_discarded(), 2);
+
+        buf.put_slice(&frame(b"payload"));
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap().payload, b"payload");
+        assert_eq!(codec.take_discarded(), 3);
+        assert_eq!(codec.take_discarded(), 0);
+
+        // A magic split across reads is found once complete
+        let mut buf = BytesMut::from(&[0xff, 0x2c, 0x9e][..]);
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        buf.put_slice(&frame(b"split")[2..]);
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap().payload, b"split");
+        assert_eq!(codec.take_discarded(), 1);
+    }
+
+    #[test]
+    fn oversized_header_fails() {
+        let mut codec = PacketCodec::new(16);
+        let mut buf = BytesMut::new();
+        buf.put_slice(&MAGIC_BYTES);
+        buf.put_u32_le(17);
+        // The rest of the frame has not arrived yet; the header alone is enough
+        let err = codec.decode(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        let mut codec = PacketCodec::new(16);
+        let mut buf = BytesMut::from(&frame(&[0u8; 16])[..]);
+        assert_eq!(codec.decode(&mut buf).unwrap().unwrap().payload.len(), 16);
+    }
+
+    #[test]
+    fn oversized_encode_fails() {
+        let mut codec = PacketCodec::new(4);
+        let mut buf = BytesMut::new();
+        assert!(codec.encode(Packet::new(vec![0; 5]), &mut buf).is_err());
+        assert!(buf.is_empty());
+    }
+}
@@ -1,27 +1,33 @@
 use super::RodeCommand;
-use byteorder::{LittleEndian, WriteBytesExt};
 
+/// `faderLevel` at base 0x04, with the FaderLevel specific preamble from capture
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "faderLevel", base = 0x04, ty = 0x05, preamble = [0x01, 0x01, 0x02, 0x00, 0x01])]
 pub struct FaderLevel {
+    #[rode(index)]
     pub fader_index: u8,
     pub level: u32,
 }
 
-impl RodeCommand for FaderLevel {
-    fn build_payload(&self, _session_id: &[u8]) -> Vec<u8> {
-        let mut payload = Vec::new();
-        // FaderLevel specific preamble from capture
-        payload.extend_from_slice(&[0x01, 0x01, 0x02, 0x00]); 
-        
-        payload.push(0x01);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{RodeDecode, assert_round_trip};
 
-        // Base 0x04
-        payload.push(0x04 + self.fader_index);
-        payload.extend_from_slice(b"faderLevel\0");
-        payload.push(0x01);
-        payload.push(0x05);
-        payload.push(0x01);
-        payload.write_u32::<LittleEndian>(self.level).unwrap();
-        
-        payload
+    #[test]
+    fn round_trip() {
+        for level in [0, 32768, 65535] {
+            assert_round_trip(FaderLevel { fader_index: 6, level }, &[0x0a, 0x0b, 0x0c, 0x0d]);
+        }
+    }
+
+    #[test]
+    fn preamble_replaces_session() {
+        let payload = FaderLevel { fader_index: 2, level: 1 }.build_payload(&[0x0a, 0x0b, 0x0c, 0x0d]);
+        assert_eq!(&payload[..6], &[0x01, 0x01, 0x02, 0x00, 0x01, 0x06]);
+        // A frame with the session where the preamble belongs is not a faderLevel write
+        let mut other = payload.clone();
+        other[..4].copy_from_slice(&[0x0a, 0x0b, 0x0c, 0x0d]);
+        assert_eq!(FaderLevel::decode_payload(&other), None);
     }
 }
@@ -0,0 +1,53 @@
+//! Generic parser for property frames.
+//!
+//! Payload layout: `[SessionID: 4B] [Prefix: 1-2B] [Property\0] [Value]`.
+//! Regular commands use a one-byte index prefix; CallMe mix routing and
+//! faderLevel use two bytes.
+
+use super::SESSION_ID_LEN;
+
+/// A property frame split into its parts
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message<'a> {
+    pub session: &'a [u8],
+    pub prefix: &'a [u8],
+    pub property: &'a str,
+    /// Everything after the property's NUL terminator
+    pub value: &'a [u8],
+}
+
+impl<'a> Message<'a> {
+    pub fn parse(payload: &'a [u8]) -> Option<Self> {
+        let session = payload.get(..SESSION_ID_LEN)?;
+
+        // The property starts at the first lowercase letter after a 1-2 byte prefix
+        for prefix_len in 1..=2 {
+            let start = SESSION_ID_LEN + prefix_len;
+            let rest = payload.get(start..)?;
+            if !rest.first()?.is_ascii_lowercase() {
+                continue;
+            }
+            let nul = rest.iter().position(|&b| b == 0)?;
+            let name = &rest[..nul];
+            if name.len() < 3 || !name.iter().all(u8::is_ascii_alphanumeric) {
+                return None;
+            }
+            return Some(Message {
+                session,
+                prefix: &payload[SESSION_ID_LEN..start],
+                // All bytes checked as ASCII above
+                property: std::str::from_utf8(name).ok()?,
+                value: &rest[nul + 1..],
+            });
+        }
+        None
+    }
+
+    /// Regular frames carry a single index byte after the session ID
+    pub fn index(&self) -> Option<u8> {
+        match self.prefix {
+            [index] => Some(*index),
+            _ => None,
+        }
+    }
+}
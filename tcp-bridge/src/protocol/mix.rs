@@ -1,17 +1,219 @@
 //! Mix routing protocol commands.
 //! 
 //! Link sends both enable + link packets to work from any state.
+//! CallMe sources use their own session ID, learned from traffic.
 
-use super::RodeCommand;
-use crate::names::Source;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use super::{Message, RodeCommand, WireValue};
+use crate::names::{MixOutput, Source};
 use crate::commands::MixAction;
 
 /// Formula: prefix = source_index * 13 + mix_index
-fn calculate_mix_prefix(source_index: u8, mix_index: u8) -> u8 {
-    source_index * 13 + mix_index
+/// (wraps for the highest sources, matching what goes on the wire)
+pub fn calculate_mix_prefix(source_index: u8, mix_index: u8) -> u8 {
+    source_index.wrapping_mul(13).wrapping_add(mix_index)
+}
+
+/// First CallMe prefix byte = base + mix_index (second byte = CallMe number).
+/// The base of current firmware; the bridge checks it against traffic.
+pub const CALLME_PREFIX_BASE: u8 = 4;
+
+/// Session ID and prefix of a CallMe mix frame (two-byte prefix ending in 1-3)
+fn callme_frame(payload: &[u8]) -> Option<(&[u8], [u8; 2])> {
+    let message = Message::parse(payload)?;
+    match message.prefix {
+        &[first, callme @ 1..=3] if message.property.starts_with("mix") => Some((message.session, [first, callme])),
+        _ => None,
+    }
+}
+
+/// Session ID of a CallMe mix frame
+pub fn extract_callme_session(payload: &[u8]) -> Option<&[u8]> {
+    callme_frame(payload).map(|(session, _)| session)
+}
+
+/// First prefix bytes of CallMe mix frames with the known base
+fn known_callme_range() -> std::ops::RangeInclusive<u8> {
+    CALLME_PREFIX_BASE + FIRST_MIX.to_index()..=CALLME_PREFIX_BASE + LAST_MIX.to_index()
+}
+
+const FIRST_MIX: MixOutput = MixOutput::Headphone1;
+const LAST_MIX: MixOutput = MixOutput::CallMe3;
+
+/// True if a CallMe mix frame's prefix falls outside the known mix range,
+/// i.e. the firmware changed the layout and the formula needs updating
+pub fn is_unexpected_callme_prefix(payload: &[u8]) -> bool {
+    callme_frame(payload).is_some_and(|(_, [first, _])| !known_callme_range().contains(&first))
+}
+
+/// Prefix layout of the CallMe channel, learned from its mix frames.
+/// Each mix has its own first prefix byte, so once frames for all mixes
+/// have been seen the lowest byte is the first mix's and gives the base.
+/// Until then the known base is used unless the frames contradict it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CallMeLayout {
+    /// Lowest and highest first prefix byte seen
+    seen: Option<(u8, u8)>,
+}
+
+impl CallMeLayout {
+    /// Record a frame; returns true if the base changed
+    pub fn observe(&mut self, payload: &[u8]) -> bool {
+        let Some((_, [first, _])) = callme_frame(payload) else { return false };
+        let before = self.prefix_base();
+        self.seen = Some(match self.seen {
+            Some((lowest, highest)) => (lowest.min(first), highest.max(first)),
+            None => (first, first),
+        });
+        self.prefix_base() != before
+    }
+
+    /// First prefix byte of mix 0, if the frames seen so far agree on one
+    pub fn prefix_base(&self) -> Option<u8> {
+        let (lowest, highest) = self.seen?;
+        let span = LAST_MIX.to_index() - FIRST_MIX.to_index();
+        match highest - lowest {
+            d if d == span => lowest.checked_sub(FIRST_MIX.to_index()),
+            d if d < span => {
+                let known = known_callme_range();
+                (known.contains(&lowest) && known.contains(&highest)).then_some(CALLME_PREFIX_BASE)
+            }
+            // More first bytes than mixes: not the layout we know
+            _ => None,
+        }
+    }
+}
+
+/// What CallMe mix frames are built from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallMeChannel {
+    pub session: Vec<u8>,
+    pub prefix_base: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MixError {
+    /// No CallMe frame has been seen yet, or the ones seen do not fit one
+    /// prefix layout, so the session ID or the prefix base is unknown
+    CallMeSessionUnknown,
+    /// No known mixDisabled encoding (enable/disable) for the two-byte CallMe prefix
+    CallMeDisableUnsupported,
+}
+
+impl fmt::Display for MixError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::CallMeSessionUnknown => write!(
+                f, "CallMe session not observed yet - change a CallMe route on the device first"
+            ),
+            Self::CallMeDisableUnsupported => write!(f, "Enable/disable is not supported for CallMe sources"),
+        }
+    }
+}
+
+impl std::error::Error for MixError {}
+
+/// `mixDisabled` for one source/mix cell: 02 = disabled, 03 = enabled
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "mixDisabled", ty = 0x01)]
+pub struct MixDisabled {
+    #[rode(index)]
+    pub prefix: u8,
+    pub disabled: bool,
+}
+
+/// Value of `mixLinkRequest` / `mixUnlinkRequest` (type 0x07):
+/// `08` followed by two bool elements (`01 01 xx` each).
+/// In unlink_capture.txt the GUI sends `02 02` for five HP1 cells but
+/// `02 03` for SoundPad; what the second element means is not known yet.
+/// The mixer echoes each request with every flag inverted, then reports
+/// the result via `mixLink`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MixRequestFlags(pub [bool; 2]);
+
+impl MixRequestFlags {
+    /// What the GUI sent for most cells in the capture; used for every request
+    pub const REQUEST: Self = MixRequestFlags([true, true]);
+}
+
+impl WireValue for MixRequestFlags {
+    fn encode(&self, payload: &mut Vec<u8>) {
+        payload.push(0x08);
+        for flag in self.0 {
+            payload.extend_from_slice(&[0x01, 0x01]);
+            flag.encode(payload);
+        }
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x08, 0x01, 0x01, a, 0x01, 0x01, b, ..] => {
+                Some(MixRequestFlags([bool::decode(&[*a])?, bool::decode(&[*b])?]))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Request to make a cell follow the source's fader
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "mixLinkRequest", ty = 0x07)]
+pub struct MixLinkRequest {
+    #[rode(index)]
+    pub prefix: u8,
+    pub flags: MixRequestFlags,
 }
 
-/// Unified mix command - handles link/unlink/disable
+/// Request to feed a cell at a fixed level
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "mixUnlinkRequest", ty = 0x07)]
+pub struct MixUnlinkRequest {
+    #[rode(index)]
+    pub prefix: u8,
+    pub flags: MixRequestFlags,
+}
+
+/// Link request for a CallMe source: two-byte prefix (mix byte, CallMe
+/// number), sent with the CallMe channel's session ID
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "mixLinkRequest", ty = 0x07)]
+pub struct CallMeLinkRequest {
+    #[rode(index)]
+    pub prefix: [u8; 2],
+    pub flags: MixRequestFlags,
+}
+
+/// Unlink request for a CallMe source (prefix as in `CallMeLinkRequest`)
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "mixUnlinkRequest", ty = 0x07)]
+pub struct CallMeUnlinkRequest {
+    #[rode(index)]
+    pub prefix: [u8; 2],
+    pub flags: MixRequestFlags,
+}
+
+/// Mixer -> GUI link state of a cell: 02 = linked, 03 = unlinked
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "mixLink", ty = 0x01)]
+pub struct MixLink {
+    #[rode(index)]
+    pub prefix: u8,
+    pub linked: bool,
+}
+
+/// Mixer -> GUI mute state of a cell: 02 = muted, 03 = unmuted
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "mixMute", ty = 0x01)]
+pub struct MixMute {
+    #[rode(index)]
+    pub prefix: u8,
+    pub muted: bool,
+}
+
+/// Unified mix command - handles link/unlink/disable/enable
 pub struct MixCommand {
     pub action: MixAction,
     pub mix_index: u8,
@@ -25,11 +227,15 @@ impl MixCommand {
     
     /// Build all payloads needed for this command
     /// Link returns two packets (enable first, then link)
-    pub fn build_payloads(&self, session_id: &[u8]) -> Vec<Vec<u8>> {
+    pub fn build_payloads(&self, session_id: &[u8], callme: Option<&CallMeChannel>) -> Result<Vec<Vec<u8>>, MixError> {
         if self.source.is_callme() {
-            vec![self.build_callme_payload()]
+            if matches!(self.action, MixAction::Disable | MixAction::Enable) {
+                return Err(MixError::CallMeDisableUnsupported);
+            }
+            let callme = callme.ok_or(MixError::CallMeSessionUnknown)?;
+            Ok(vec![self.build_callme_payload(callme)])
         } else {
-            match self.action {
+            Ok(match self.action {
                 MixAction::Link => {
                     // Send enable first (to handle Disabled state), then link
                     vec![
@@ -37,72 +243,174 @@ impl MixCommand {
                         self.build_link_payload(session_id),
                     ]
                 }
+                // Only clears mixDisabled - link mode and fixed level are kept
+                MixAction::Enable => vec![self.build_enable_payload(session_id)],
                 _ => vec![self.build_regular_payload(session_id)],
-            }
+            })
         }
     }
     
+    fn prefix(&self) -> u8 {
+        calculate_mix_prefix(self.source.to_index(), self.mix_index)
+    }
+
     fn build_enable_payload(&self, session_id: &[u8]) -> Vec<u8> {
-        let mut payload = Vec::new();
-        payload.extend_from_slice(session_id);
-        payload.push(calculate_mix_prefix(self.source.to_index(), self.mix_index));
-        payload.extend_from_slice(b"mixDisabled\0");
-        payload.extend_from_slice(&[0x01, 0x01, 0x03]); // 03 = enabled
-        payload
+        MixDisabled { prefix: self.prefix(), disabled: false }.build_payload(session_id)
     }
     
     fn build_link_payload(&self, session_id: &[u8]) -> Vec<u8> {
-        let mut payload = Vec::new();
-        payload.extend_from_slice(session_id);
-        payload.push(calculate_mix_prefix(self.source.to_index(), self.mix_index));
-        payload.extend_from_slice(b"mixLinkRequest\0");
-        payload.extend_from_slice(&[0x01, 0x07, 0x08, 0x01, 0x01, 0x02, 0x01, 0x01, 0x02]);
-        payload
+        MixLinkRequest { prefix: self.prefix(), flags: MixRequestFlags::REQUEST }.build_payload(session_id)
     }
     
     fn build_regular_payload(&self, session_id: &[u8]) -> Vec<u8> {
-        let mut payload = Vec::new();
-        payload.extend_from_slice(session_id);
-        payload.push(calculate_mix_prefix(self.source.to_index(), self.mix_index));
-        
         match self.action {
-            MixAction::Link => unreachable!(), // Handled separately
+            MixAction::Link | MixAction::Enable => unreachable!(), // Handled separately
             MixAction::Unlink => {
-                payload.extend_from_slice(b"mixUnlinkRequest\0");
-                payload.extend_from_slice(&[0x01, 0x07, 0x08, 0x01, 0x01, 0x02, 0x01, 0x01, 0x02]);
+                MixUnlinkRequest { prefix: self.prefix(), flags: MixRequestFlags::REQUEST }.build_payload(session_id)
             }
             MixAction::Disable => {
-                payload.extend_from_slice(b"mixDisabled\0");
-                payload.extend_from_slice(&[0x01, 0x01, 0x02]); // 02 = disabled
+                MixDisabled { prefix: self.prefix(), disabled: true }.build_payload(session_id)
             }
         }
-        payload
     }
 
-    fn build_callme_payload(&self) -> Vec<u8> {
-        let mut payload = Vec::new();
-        payload.extend_from_slice(&[0x01, 0x01, 0x01, 0x02]); // Special session ID
-        payload.push(4 + self.mix_index);
-        payload.push(self.source.to_index());
-        
+    fn build_callme_payload(&self, callme: &CallMeChannel) -> Vec<u8> {
+        let prefix = [callme.prefix_base.wrapping_add(self.mix_index), self.source.to_index()];
+        let flags = MixRequestFlags::REQUEST;
         match self.action {
-            MixAction::Link => {
-                payload.extend_from_slice(b"mixLinkRequest\0");
-                payload.extend_from_slice(&[0x01, 0x07, 0x08, 0x01, 0x01, 0x02, 0x01, 0x01, 0x02]);
-            }
-            MixAction::Unlink => {
-                payload.extend_from_slice(b"mixUnlinkRequest\0");
-                payload.extend_from_slice(&[0x01, 0x07, 0x08, 0x01, 0x01, 0x02, 0x01, 0x01, 0x02]);
-            }
-            MixAction::Disable => {} // Not supported for CallMe
+            MixAction::Link => CallMeLinkRequest { prefix, flags }.build_payload(&callme.session),
+            MixAction::Unlink => CallMeUnlinkRequest { prefix, flags }.build_payload(&callme.session),
+            MixAction::Disable | MixAction::Enable => unreachable!(), // Rejected in build_payloads
         }
-        payload
     }
 }
 
-// Keep RodeCommand impl for backward compat (uses first payload only)
+// Keep RodeCommand impl for backward compat (uses first payload only,
+// empty for CallMe sources whose session is unknown here)
 impl RodeCommand for MixCommand {
     fn build_payload(&self, session_id: &[u8]) -> Vec<u8> {
-        self.build_payloads(session_id).into_iter().next().unwrap_or_default()
+        self.build_payloads(session_id, None).ok()
+            .and_then(|p| p.into_iter().next())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{RodeDecode, assert_round_trip};
+
+    const SESSION: [u8; 4] = [0x0a, 0x0b, 0x0c, 0x0d];
+
+    #[test]
+    fn round_trip() {
+        for prefix in [0x00, 0x58, 0xcd, 0xff] {
+            for state in [true, false] {
+                assert_round_trip(MixDisabled { prefix, disabled: state }, &SESSION);
+                assert_round_trip(MixLink { prefix, linked: state }, &SESSION);
+                assert_round_trip(MixMute { prefix, muted: state }, &SESSION);
+            }
+            for flags in [[true, true], [true, false], [false, true], [false, false]] {
+                let flags = MixRequestFlags(flags);
+                assert_round_trip(MixLinkRequest { prefix, flags }, &SESSION);
+                assert_round_trip(MixUnlinkRequest { prefix, flags }, &SESSION);
+            }
+        }
+    }
+
+    #[test]
+    fn callme_round_trip() {
+        for mix_index in 0..13 {
+            for callme in 1..=3 {
+                let prefix = [CALLME_PREFIX_BASE + mix_index, callme];
+                let flags = MixRequestFlags::REQUEST;
+                assert_round_trip(CallMeLinkRequest { prefix, flags }, &SESSION);
+                assert_round_trip(CallMeUnlinkRequest { prefix, flags }, &SESSION);
+            }
+        }
+    }
+
+    /// One- and two-byte prefixes must not be mistaken for each other
+    #[test]
+    fn prefix_length_is_distinct() {
+        let flags = MixRequestFlags::REQUEST;
+        let regular = MixLinkRequest { prefix: 0x58, flags }.build_payload(&SESSION);
+        let callme = CallMeLinkRequest { prefix: [0x10, 0x02], flags }.build_payload(&SESSION);
+        assert_eq!(CallMeLinkRequest::decode_payload(&regular), None);
+        assert_eq!(MixLinkRequest::decode_payload(&callme), None);
+    }
+
+    /// A payload with bytes after the value is a different message
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        let mut payload = MixLink { prefix: 0x58, linked: true }.build_payload(&SESSION);
+        payload.push(0x00);
+        assert_eq!(MixLink::decode_payload(&payload), None);
+
+        let mut payload = CallMeLinkRequest { prefix: [0x10, 0x02], flags: MixRequestFlags::REQUEST }.build_payload(&SESSION);
+        payload.push(0x02);
+        assert_eq!(CallMeLinkRequest::decode_payload(&payload), None);
+    }
+
+    fn callme_frame(first: u8) -> Vec<u8> {
+        CallMeLinkRequest { prefix: [first, 1], flags: MixRequestFlags::REQUEST }.build_payload(&SESSION)
+    }
+
+    #[test]
+    fn callme_layout_is_learned() {
+        let mut layout = CallMeLayout::default();
+        assert_eq!(layout.prefix_base(), None);
+        // A few mixes in the known range keep the known base
+        layout.observe(&callme_frame(CALLME_PREFIX_BASE + 12));
+        assert_eq!(layout.prefix_base(), Some(CALLME_PREFIX_BASE));
+
+        // Bytes outside it leave the base unknown until all mixes show up
+        let mut layout = CallMeLayout::default();
+        layout.observe(&callme_frame(0x30));
+        assert_eq!(layout.prefix_base(), None);
+        assert!(is_unexpected_callme_prefix(&callme_frame(0x30)));
+        assert!(layout.observe(&callme_frame(0x30 + 12)));
+        assert_eq!(layout.prefix_base(), Some(0x30 - 10));
+
+        let callme = CallMeChannel { session: SESSION.to_vec(), prefix_base: 0x30 - 10 };
+        let payloads = MixCommand::new(MixAction::Link, 10, Source::CallMe2).build_payloads(&[0x01; 4], Some(&callme));
+        assert_eq!(payloads, Ok(vec![CallMeLinkRequest { prefix: [0x30, 2], flags: MixRequestFlags::REQUEST }.build_payload(&SESSION)]));
+    }
+
+    /// Only mix frames carry the CallMe layout
+    #[test]
+    fn other_two_byte_prefixes_are_ignored() {
+        let mut payload = callme_frame(0x30);
+        payload.splice(6..21, b"faderLevel\0".iter().copied());
+        let mut layout = CallMeLayout::default();
+        assert!(!layout.observe(&payload));
+        assert!(!is_unexpected_callme_prefix(&payload));
+    }
+
+    /// C->S unlink request from unlink_capture.txt (prefix b3)
+    #[test]
+    fn decodes_captured_unlink_request() {
+        let payload = hex::decode("01010101b36d6978556e6c696e6b5265717565737400010708010102010102").unwrap();
+        assert_eq!(
+            MixUnlinkRequest::decode_payload(&payload),
+            Some(MixUnlinkRequest { prefix: 0xb3, flags: MixRequestFlags([true, true]) })
+        );
+        assert_eq!(MixUnlinkRequest { prefix: 0xb3, flags: MixRequestFlags::REQUEST }.build_payload(&[0x01; 4]), payload);
+    }
+
+    /// The SoundPad -> HP1 request (prefix cd) clears the second flag, and
+    /// the mixer's echo inverts each flag
+    #[test]
+    fn decodes_captured_second_flag() {
+        let request = hex::decode("01010101cd6d6978556e6c696e6b5265717565737400010708010102010103").unwrap();
+        let echo = hex::decode("01010101cd6d6978556e6c696e6b5265717565737400010708010103010102").unwrap();
+        assert_eq!(
+            MixUnlinkRequest::decode_payload(&request),
+            Some(MixUnlinkRequest { prefix: 0xcd, flags: MixRequestFlags([true, false]) })
+        );
+        assert_eq!(
+            MixUnlinkRequest::decode_payload(&echo),
+            Some(MixUnlinkRequest { prefix: 0xcd, flags: MixRequestFlags([false, true]) })
+        );
     }
 }
@@ -6,12 +6,21 @@ pub mod source;
 pub mod level;
 pub mod touch;
 pub mod mix;
+pub mod codec;
+pub mod message;
+pub mod spec;
+pub mod value;
 
 pub use mute::*;
 pub use source::*;
 pub use level::*;
 pub use touch::*;
 pub use mix::*;
+pub use codec::*;
+pub use message::*;
+pub use spec::*;
+pub use value::*;
+pub use rode_derive::RodeCommand;
 
 #[derive(Debug, Clone)]
 pub struct Packet {
@@ -21,9 +30,12 @@ pub struct Packet {
 }
 
 impl Packet {
+    /// Magic header (`2c 9e b4 f2` on the wire)
+    pub const MAGIC: u32 = 0xF2B49E2C;
+
     pub fn new(payload: Vec<u8>) -> Self {
         Packet {
-            header: 0xF2B49E2C, // Magic header
+            header: Self::MAGIC,
             length: payload.len() as u32,
             payload,
         }
@@ -38,19 +50,52 @@ impl Packet {
     }
 }
 
+/// Length of the session ID at the start of every payload
+pub const SESSION_ID_LEN: usize = 4;
+
+/// Session ID used before one is sniffed (`--allow-default-session`, dry runs)
+pub const DEFAULT_SESSION_ID: [u8; SESSION_ID_LEN] = [0x01; SESSION_ID_LEN];
+
 pub trait RodeCommand {
     fn build_payload(&self, session_id: &[u8]) -> Vec<u8>;
 }
 
+/// Inverse of `RodeCommand`: parse a payload back into the command
+pub trait RodeDecode: Sized {
+    fn decode_payload(payload: &[u8]) -> Option<Self>;
+}
+
+/// Encode, decode and compare; used by the derived commands' tests
+#[cfg(test)]
+pub(crate) fn assert_round_trip<T>(command: T, session_id: &[u8])
+where
+    T: RodeCommand + RodeDecode + PartialEq + std::fmt::Debug,
+{
+    let payload = command.build_payload(session_id);
+    assert_eq!(T::decode_payload(&payload), Some(command), "payload {}", hex::encode(&payload));
+}
+
+/// Keepalive keyword, in place of the session ID. The GUI sends it and the
+/// mixer answers with the same payload; no other keyword has been seen in
+/// either direction.
+pub const PING: &[u8] = b"ping";
+
+/// A keepalive frame: the GUI's ping (C->S) or the mixer's answer (S->C)
+pub fn is_ping(payload: &[u8]) -> bool {
+    payload.starts_with(PING)
+}
+
+/// Session ID of a regular (one-byte index) property frame.
+/// Pings, CallMe and faderLevel frames carry other values in that position.
 pub fn extract_session_id(data: &[u8]) -> Option<Vec<u8>> {
-    // Preamble: 2c 9e b4 f2
-    if data.len() >= 12 
-       && data[0] == 0x2c && data[1] == 0x9e && data[2] == 0xb4 && data[3] == 0xf2 
-    {
-        // Check for "ping" (70 69 6e 67) at offset 8 (no session id)
-        if !(data[8] == 0x70 && data[9] == 0x69 && data[10] == 0x6e && data[11] == 0x67) {
-             return Some(data[8..12].to_vec());
-        }
+    if data.len() < 8 || data[..4] != Packet::MAGIC.to_le_bytes() {
+        return None;
+    }
+    let payload = &data[8..];
+    if is_ping(payload) {
+        return None;
     }
-    None
+    let message = Message::parse(payload)?;
+    message.index()?;
+    Some(message.session.to_vec())
 }
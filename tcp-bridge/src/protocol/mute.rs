@@ -1,22 +1,34 @@
 use super::RodeCommand;
 
+/// `channelOutputMute` at base 0x1C: 02 = muted, 03 = unmuted
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "channelOutputMute", base = 0x1C, ty = 0x01)]
 pub struct ChannelOutputMute {
+    #[rode(index)]
     pub fader_index: u8,
     pub mute: bool,
 }
 
-impl RodeCommand for ChannelOutputMute {
-    fn build_payload(&self, session_id: &[u8]) -> Vec<u8> {
-        let mut payload = Vec::new();
-        payload.extend_from_slice(session_id);
-        
-        // Base 0x1C
-        payload.push(0x1C + self.fader_index);
-        payload.extend_from_slice(b"channelOutputMute\0");
-        payload.push(0x01); // Type
-        payload.push(0x01); // Val
-        payload.push(if self.mute { 0x02 } else { 0x03 });
-        
-        payload
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{RodeDecode, assert_round_trip};
+
+    #[test]
+    fn round_trip() {
+        for fader_index in 0..9 {
+            for mute in [true, false] {
+                assert_round_trip(ChannelOutputMute { fader_index, mute }, &[0x0a, 0x0b, 0x0c, 0x0d]);
+            }
+        }
+    }
+
+    #[test]
+    fn decodes_wire_bytes() {
+        let payload = hex::decode("010101011c6368616e6e656c4f75747075744d75746500010102").unwrap();
+        assert_eq!(
+            ChannelOutputMute::decode_payload(&payload),
+            Some(ChannelOutputMute { fader_index: 0, mute: true })
+        );
     }
 }
@@ -1,46 +1,37 @@
 use super::RodeCommand;
-use byteorder::{LittleEndian, WriteBytesExt};
 
+/// `channelInputSource` at base 0x1C (Type: Integer)
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "channelInputSource", base = 0x1C, ty = 0x05)]
 pub struct ChannelInputSource {
+    #[rode(index)]
     pub fader_index: u8,
     pub source_id: u32,
 }
 
-impl RodeCommand for ChannelInputSource {
-    fn build_payload(&self, session_id: &[u8]) -> Vec<u8> {
-        let mut payload = Vec::new();
-        payload.extend_from_slice(session_id);
-        
-        // Base 0x1C
-        payload.push(0x1C + self.fader_index);
-        payload.extend_from_slice(b"channelInputSource\0");
-        payload.push(0x01);
-        payload.push(0x05); // Type: Integer
-        payload.push(0x01); // Count
-        payload.write_u32::<LittleEndian>(self.source_id).unwrap();
-        
-        payload
-    }
-}
-
+/// `inputMicrophoneType` at base 0x1C (-1 is sent as 0xFFFFFFFF)
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "inputMicrophoneType", base = 0x1C, ty = 0x05)]
 pub struct InputMicrophoneType {
+    #[rode(index)]
     pub fader_index: u8,
     pub mic_type: u32,
 }
 
-impl RodeCommand for InputMicrophoneType {
-    fn build_payload(&self, session_id: &[u8]) -> Vec<u8> {
-        let mut payload = Vec::new();
-        payload.extend_from_slice(session_id);
-        
-        // Base 0x1C
-        payload.push(0x1C + self.fader_index);
-        payload.extend_from_slice(b"inputMicrophoneType\0");
-        payload.push(0x01);
-        payload.push(0x05);
-        payload.push(0x01);
-        payload.write_u32::<LittleEndian>(self.mic_type).unwrap();
-        
-        payload
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::assert_round_trip;
+
+    #[test]
+    fn round_trip() {
+        let session = [0x0a, 0x0b, 0x0c, 0x0d];
+        for source_id in [0, 11, 16, u32::MAX] {
+            assert_round_trip(ChannelInputSource { fader_index: 3, source_id }, &session);
+        }
+        // -1 and 4 follow every source change
+        for mic_type in [-1i32 as u32, 4] {
+            assert_round_trip(InputMicrophoneType { fader_index: 8, mic_type }, &session);
+        }
     }
 }
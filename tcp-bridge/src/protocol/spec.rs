@@ -0,0 +1,324 @@
+//! Declarative command definitions loaded from a JSON spec file.
+//!
+//! Every property write follows the same layout:
+//! `[Preamble] [Index] [Property\0] [0x01] [Type] [Value]`.
+//! A spec entry describes those parts as data, so newly reverse-engineered
+//! controls can be added on the device by editing the spec file instead of
+//! recompiling the bridge.
+
+use byteorder::{LittleEndian, WriteBytesExt};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
+
+/// How the value after the type byte is encoded
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ValueEncoding {
+    /// Single state byte: non-zero = `0x02`, zero = `0x03`
+    Bool,
+    /// Count byte followed by a little-endian u32
+    U32,
+    /// Count byte followed by a little-endian i32 (e.g. mic type -1)
+    I32,
+}
+
+/// A command sent right after its parent, on the same index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FollowUp {
+    pub command: String,
+    pub value: i64,
+}
+
+/// One command definition from the spec file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CommandSpec {
+    /// Name used by clients (`bridge-ctl spec <name> ...`)
+    pub name: String,
+    /// Property string on the wire, without the trailing NUL
+    pub property: String,
+    /// Fixed bytes written instead of the session ID
+    /// (e.g. `[1, 1, 2, 0, 1]` for faderLevel)
+    #[serde(default)]
+    pub preamble: Option<Vec<u8>>,
+    /// Added to the caller's index to form the index byte
+    #[serde(default)]
+    pub index_base: u8,
+    /// Inclusive range of valid caller indices
+    #[serde(default)]
+    pub index_range: Option<[u8; 2]>,
+    /// Type byte (0x01 = bool, 0x05 = integer)
+    pub ty: u8,
+    /// Count byte for integer encodings
+    #[serde(default = "default_count")]
+    pub count: u8,
+    pub encoding: ValueEncoding,
+    /// Value used when the caller gives none (e.g. screenTouched)
+    #[serde(default)]
+    pub default_value: Option<i64>,
+    #[serde(default)]
+    pub follow_up: Vec<FollowUp>,
+}
+
+fn default_count() -> u8 {
+    0x01
+}
+
+impl ValueEncoding {
+    /// Values that fit the encoding; anything else is refused, not wrapped
+    fn check(&self, value: i64) -> Result<(), String> {
+        let (min, max) = match self {
+            ValueEncoding::Bool => (0, 1),
+            ValueEncoding::U32 => (0, u32::MAX as i64),
+            ValueEncoding::I32 => (i32::MIN as i64, i32::MAX as i64),
+        };
+        if (min..=max).contains(&value) {
+            Ok(())
+        } else {
+            Err(format!("Value {} out of range ({}-{})", value, min, max))
+        }
+    }
+}
+
+impl CommandSpec {
+    /// Build the payload for one invocation of this command.
+    /// `index` and `value` must have passed `check_index` and `check_value`.
+    pub fn build_payload(&self, index: u8, value: i64, session_id: &[u8]) -> Vec<u8> {
+        let mut payload = Vec::new();
+        match &self.preamble {
+            Some(bytes) => payload.extend_from_slice(bytes),
+            None => payload.extend_from_slice(session_id),
+        }
+
+        payload.push(self.index_base.wrapping_add(index));
+        payload.extend_from_slice(self.property.as_bytes());
+        payload.push(0x00);
+        payload.push(0x01);
+        payload.push(self.ty);
+
+        match self.encoding {
+            ValueEncoding::Bool => payload.push(if value != 0 { 0x02 } else { 0x03 }),
+            ValueEncoding::U32 => {
+                payload.push(self.count);
+                payload.write_u32::<LittleEndian>(value.clamp(0, u32::MAX as i64) as u32).unwrap();
+            }
+            ValueEncoding::I32 => {
+                payload.push(self.count);
+                payload.write_i32::<LittleEndian>(value.clamp(i32::MIN as i64, i32::MAX as i64) as i32).unwrap();
+            }
+        }
+        payload
+    }
+
+    fn check_index(&self, index: u8) -> Result<(), String> {
+        match self.index_range {
+            Some([min, max]) if index < min || index > max => Err(format!(
+                "Index {} out of range for {} ({}-{})", index, self.name, min, max
+            )),
+            _ if self.index_base.checked_add(index).is_none() => Err(format!(
+                "Index {} overflows the index base of {} ({})", index, self.name, self.index_base
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// The range itself must be valid and fit on top of the base
+    fn check_range(&self) -> Result<(), String> {
+        match self.index_range {
+            Some([min, max]) if min > max => Err(format!(
+                "{}: index_range {}-{} is empty", self.name, min, max
+            )),
+            Some([_, max]) => self.check_index(max).map_err(|e| format!("{}: {}", self.name, e)),
+            None => Ok(()),
+        }
+    }
+
+    fn check_value(&self, value: i64) -> Result<(), String> {
+        self.encoding.check(value).map_err(|e| format!("{} for {}", e, self.name))
+    }
+}
+
+/// A resolved spec command, ready to be built once the session is known.
+/// Carries its spec along, so a handover keeps what was injected.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SpecInvocation {
+    pub spec: Arc<CommandSpec>,
+    pub index: u8,
+    pub value: i64,
+}
+
+impl SpecInvocation {
+    pub fn build_payload(&self, session_id: &[u8]) -> Vec<u8> {
+        self.spec.build_payload(self.index, self.value, session_id)
+    }
+}
+
+#[derive(Deserialize)]
+struct SpecFile {
+    commands: Vec<CommandSpec>,
+}
+
+/// All command definitions loaded at startup
+#[derive(Debug, Clone, Default)]
+pub struct CommandSpecs {
+    commands: Vec<Arc<CommandSpec>>,
+}
+
+impl CommandSpecs {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, String> {
+        let file: SpecFile = serde_json::from_str(text)
+            .map_err(|e| format!("Invalid command spec: {}", e))?;
+        let specs = CommandSpecs {
+            commands: file.commands.into_iter().map(Arc::new).collect(),
+        };
+
+        // Catch typos in follow-up names and bad fixed values at load time
+        // rather than at injection
+        for spec in &specs.commands {
+            spec.check_range()?;
+            if let Some(value) = spec.default_value {
+                spec.check_value(value)?;
+            }
+            for follow in &spec.follow_up {
+                let Some(follow_spec) = specs.get(&follow.command) else {
+                    return Err(format!(
+                        "{}: unknown follow-up command {}", spec.name, follow.command
+                    ));
+                };
+                follow_spec.check_value(follow.value)
+                    .map_err(|e| format!("{}: follow-up {}", spec.name, e))?;
+            }
+        }
+        Ok(specs)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Arc<CommandSpec>> {
+        self.commands.iter().find(|c| c.name == name)
+    }
+
+    pub fn len(&self) -> usize {
+        self.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.commands.is_empty()
+    }
+
+    /// Resolve a named command and its follow-ups into invocations
+    pub fn resolve(&self, name: &str, index: u8, value: Option<i64>) -> Result<Vec<SpecInvocation>, String> {
+        let spec = self.get(name)
+            .ok_or_else(|| format!("Unknown spec command: {}", name))?;
+        spec.check_index(index)?;
+        let value = value.or(spec.default_value)
+            .ok_or_else(|| format!("{} requires a value", name))?;
+        spec.check_value(value)?;
+
+        let mut steps = vec![SpecInvocation { spec: spec.clone(), index, value }];
+        for follow in &spec.follow_up {
+            // Name and value validated in parse(); the index depends on the call
+            let follow_spec = self.get(&follow.command).unwrap();
+            follow_spec.check_index(index)
+                .map_err(|e| format!("{}: follow-up {}", name, e))?;
+            steps.push(SpecInvocation { spec: follow_spec.clone(), index, value: follow.value });
+        }
+        Ok(steps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SESSION: [u8; 4] = [0x0a, 0x0b, 0x0c, 0x0d];
+
+    const SPECS: &str = r#"{"commands": [
+        {"name": "source", "property": "channelInputSource", "index_base": 28, "index_range": [0, 8],
+         "ty": 5, "encoding": "u32",
+         "follow_up": [{"command": "mic_type", "value": -1}, {"command": "mic_type", "value": 4}]},
+        {"name": "mic_type", "property": "inputMicrophoneType", "index_base": 28, "index_range": [0, 4],
+         "ty": 5, "encoding": "i32"},
+        {"name": "touch", "property": "screenTouched", "index_base": 7, "index_range": [0, 0],
+         "ty": 1, "encoding": "bool", "default_value": 1}
+    ]}"#;
+
+    fn spec(name: &str, extra: &str) -> String {
+        format!(
+            r#"{{"commands": [{{"name": "{}", "property": "p", "ty": 5, "encoding": "u32"{}}}]}}"#,
+            name, extra
+        )
+    }
+
+    #[test]
+    fn load_time_validation() {
+        assert_eq!(CommandSpecs::parse(SPECS).unwrap().len(), 3);
+
+        let cases = [
+            (spec("a", r#", "follow_up": [{"command": "b", "value": 1}]"#), "a: unknown follow-up command b"),
+            (spec("a", r#", "follow_up": [{"command": "a", "value": -1}]"#), "a: follow-up Value -1 out of range"),
+            (spec("a", r#", "default_value": -1"#), "Value -1 out of range"),
+            (spec("a", r#", "index_range": [3, 2]"#), "a: index_range 3-2 is empty"),
+            (spec("a", r#", "index_base": 250, "index_range": [0, 8]"#), "a: Index 8 overflows"),
+        ];
+        for (text, error) in cases {
+            let e = CommandSpecs::parse(&text).unwrap_err();
+            assert!(e.starts_with(error), "{} -> {}", text, e);
+        }
+        assert!(CommandSpecs::parse("{}").unwrap_err().starts_with("Invalid command spec"));
+    }
+
+    #[test]
+    fn resolve_adds_follow_ups() {
+        let specs = CommandSpecs::parse(SPECS).unwrap();
+        let steps = specs.resolve("source", 2, Some(7)).unwrap();
+        let steps: Vec<_> = steps.iter().map(|s| (s.spec.name.as_str(), s.index, s.value)).collect();
+        assert_eq!(steps, [("source", 2, 7), ("mic_type", 2, -1), ("mic_type", 2, 4)]);
+
+        let touch = specs.resolve("touch", 0, None).unwrap();
+        assert_eq!(touch[0].value, 1);
+        assert_eq!(specs.resolve("source", 2, None).unwrap_err(), "source requires a value");
+        assert_eq!(specs.resolve("nope", 0, Some(1)).unwrap_err(), "Unknown spec command: nope");
+    }
+
+    #[test]
+    fn index_and_value_checks() {
+        let specs = CommandSpecs::parse(SPECS).unwrap();
+        assert_eq!(specs.resolve("source", 9, Some(1)).unwrap_err(), "Index 9 out of range for source (0-8)");
+        // In range for the parent but not for its follow-up
+        assert_eq!(
+            specs.resolve("source", 5, Some(1)).unwrap_err(),
+            "source: follow-up Index 5 out of range for mic_type (0-4)"
+        );
+        assert_eq!(specs.resolve("touch", 0, Some(2)).unwrap_err(), "Value 2 out of range (0-1) for touch");
+        assert_eq!(
+            specs.resolve("mic_type", 0, Some(i32::MIN as i64 - 1)).unwrap_err(),
+            "Value -2147483649 out of range (-2147483648-2147483647) for mic_type"
+        );
+
+        // Without a range only the base limits the index
+        let specs = CommandSpecs::parse(&spec("a", r#", "index_base": 250"#)).unwrap();
+        assert!(specs.resolve("a", 5, Some(0)).is_ok());
+        assert_eq!(specs.resolve("a", 6, Some(0)).unwrap_err(), "Index 6 overflows the index base of a (250)");
+    }
+
+    #[test]
+    fn encodings() {
+        let specs = CommandSpecs::parse(SPECS).unwrap();
+        let payload = |name: &str, index: u8, value: i64| {
+            specs.resolve(name, index, Some(value)).unwrap()[0].build_payload(&SESSION)
+        };
+
+        let mic_type = payload("mic_type", 1, -1);
+        assert_eq!(&mic_type[..5], [0x0a, 0x0b, 0x0c, 0x0d, 29]);
+        assert!(mic_type.ends_with(b"inputMicrophoneType\0\x01\x05\x01\xff\xff\xff\xff"));
+        assert!(payload("mic_type", 1, i32::MIN as i64).ends_with(&[0x01, 0x00, 0x00, 0x00, 0x80]));
+        assert!(payload("source", 0, 0x01020304).ends_with(&[0x05, 0x01, 0x04, 0x03, 0x02, 0x01]));
+        assert!(payload("touch", 0, 1).ends_with(b"screenTouched\0\x01\x01\x02"));
+        assert!(payload("touch", 0, 0).ends_with(&[0x03]));
+    }
+}
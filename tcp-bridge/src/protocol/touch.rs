@@ -1,15 +1,24 @@
 use super::RodeCommand;
 
+/// `screenTouched` at fixed index 0x07
+#[derive(RodeCommand, Debug, PartialEq, Eq)]
+#[rode(property = "screenTouched", base = 0x07, ty = 0x01, value = true)]
 pub struct ScreenTouched;
-impl RodeCommand for ScreenTouched {
-    fn build_payload(&self, session_id: &[u8]) -> Vec<u8> {
-        let mut payload = Vec::new();
-        payload.extend_from_slice(session_id);
-        payload.push(0x07); 
-        payload.extend_from_slice(b"screenTouched\0"); 
-        payload.push(0x01);
-        payload.push(0x01);
-        payload.push(0x02);
-        payload
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{RodeDecode, assert_round_trip};
+
+    #[test]
+    fn round_trip() {
+        assert_round_trip(ScreenTouched, &[0x0a, 0x0b, 0x0c, 0x0d]);
+    }
+
+    #[test]
+    fn rejects_other_value() {
+        let mut payload = ScreenTouched.build_payload(&[0x01; 4]);
+        *payload.last_mut().unwrap() = 0x03;
+        assert_eq!(ScreenTouched::decode_payload(&payload), None);
     }
 }
@@ -0,0 +1,41 @@
+//! Value encodings shared by derived commands.
+//!
+//! Values follow the `[0x01] [Type]` bytes after the property name.
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+
+/// A value that can be written after the type byte and read back
+pub trait WireValue: Sized {
+    fn encode(&self, payload: &mut Vec<u8>);
+    fn decode(data: &[u8]) -> Option<Self>;
+}
+
+/// Bool state byte: `0x02` = true (muted/disabled/touched), `0x03` = false
+impl WireValue for bool {
+    fn encode(&self, payload: &mut Vec<u8>) {
+        payload.push(if *self { 0x02 } else { 0x03 });
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        match data.first()? {
+            0x02 => Some(true),
+            0x03 => Some(false),
+            _ => None,
+        }
+    }
+}
+
+/// Integer: count byte (always 1) followed by a little-endian u32
+impl WireValue for u32 {
+    fn encode(&self, payload: &mut Vec<u8>) {
+        payload.push(0x01); // Count
+        payload.write_u32::<LittleEndian>(*self).unwrap();
+    }
+
+    fn decode(data: &[u8]) -> Option<Self> {
+        match data {
+            [0x01, rest @ ..] if rest.len() >= 4 => Some(LittleEndian::read_u32(rest)),
+            _ => None,
+        }
+    }
+}
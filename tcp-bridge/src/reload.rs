@@ -0,0 +1,65 @@
+//! Config reload on SIGHUP or `Command::Reload`, without dropping connections.
+//!
+//! The config file (with the environment and this process's flags on top)
+//! and the spec file are read again. Logging, pacing, feature flags, the
+//! ping timeout and the control socket's mode apply from the next frame or
+//! command. Settings in `config::RESTART_REQUIRED` keep their value and are
+//! reported. An invalid config or spec file changes nothing.
+
+use std::path::Path;
+use std::sync::Arc;
+
+use tcp_bridge::commands::ReloadReply;
+use tcp_bridge::config::Config;
+use tcp_bridge::protocol::CommandSpecs;
+
+use crate::listener;
+use crate::state::SharedState;
+
+/// Loads the config the way the process did at startup
+pub type Loader = Arc<dyn Fn() -> Result<Config, String> + Send + Sync>;
+
+pub fn reload(load: &Loader, state: &SharedState) -> ReloadReply {
+    match try_reload(load, state) {
+        Ok(reply) => reply,
+        Err(e) => {
+            eprintln!("[Config] Reload failed, keeping the current config: {}", e);
+            ReloadReply { error: Some(e), ..Default::default() }
+        }
+    }
+}
+
+fn try_reload(load: &Loader, state: &SharedState) -> Result<ReloadReply, String> {
+    let new = load()?;
+    let specs = load_specs(&new.proxy.spec)?;
+    let mut state = state.lock().unwrap();
+    let reload = state.config.reload(&new)?;
+    let reply = ReloadReply {
+        applied: reload.applied,
+        restart_required: reload.restart_required,
+        not_used: reload.not_used,
+        specs: specs.len(),
+        error: None,
+    };
+    if reply.applied.iter().any(|s| s == "control.socket_mode") {
+        listener::set_socket_mode(&reload.config.control);
+    }
+    state.reconfigure(reload.config, specs);
+
+    println!("[Config] Reloaded, {} command specs from {}", reply.specs, new.proxy.spec.display());
+    if !reply.applied.is_empty() {
+        println!("[Config] Applied: {}", reply.applied.join(", "));
+    }
+    if !reply.restart_required.is_empty() {
+        eprintln!("[Config] Needs a restart, keeping the current value: {}", reply.restart_required.join(", "));
+    }
+    Ok(reply)
+}
+
+/// Like at startup, no spec file means no specs; a broken one is an error
+fn load_specs(path: &Path) -> Result<CommandSpecs, String> {
+    if !path.exists() {
+        return Ok(CommandSpecs::default());
+    }
+    CommandSpecs::load(path)
+}
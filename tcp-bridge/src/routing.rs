@@ -0,0 +1,568 @@
+//! Routing matrix of `Source` x `MixOutput` cells.
+//!
+//! The bridge fills it from mixer traffic (`mixLink`, `mixDisabled`) so
+//! routing requests can be compared against what the mixer reports.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use crate::commands::{self, CommandError, CommandWarning, MixAction};
+use crate::names::{MixOutput, Source};
+use crate::protocol::{self, Message, RodeDecode};
+
+/// State of one source in one mix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MixState {
+    Linked,
+    Unlinked,
+    Disabled,
+}
+
+impl FromStr for MixState {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "linked" | "link" | "on" => Ok(Self::Linked),
+            "unlinked" | "unlink" | "fixed" => Ok(Self::Unlinked),
+            "disabled" | "disable" | "off" => Ok(Self::Disabled),
+            _ => Err(format!("Unknown mix state: {} (try: linked, unlinked, disabled)", s)),
+        }
+    }
+}
+
+impl fmt::Display for MixState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", match self {
+            Self::Linked => "linked",
+            Self::Unlinked => "unlinked",
+            Self::Disabled => "disabled",
+        })
+    }
+}
+
+/// Map a frame prefix back to its cell; CallMe cells need the CallMe prefix base
+pub fn cell_for_prefix(prefix: &[u8], callme_base: Option<u8>) -> Option<(MixOutput, Source)> {
+    MixOutput::ALL.iter().flat_map(|&mix| Source::ALL.iter().map(move |&src| (mix, src)))
+        .find(|(mix, src)| match prefix {
+            [p] if !src.is_callme() => *p == protocol::calculate_mix_prefix(src.to_index(), mix.to_index()),
+            [p, n] if src.is_callme() => {
+                Some(*p) == callme_base.map(|base| base.wrapping_add(mix.to_index())) && *n == src.to_index()
+            }
+            _ => false,
+        })
+}
+
+/// What we know about one cell; either part may not have been seen yet
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Cell {
+    linked: Option<bool>,
+    disabled: Option<bool>,
+}
+
+impl Cell {
+    fn state(&self) -> Option<MixState> {
+        match (self.disabled, self.linked) {
+            (Some(true), _) => Some(MixState::Disabled),
+            (_, Some(true)) => Some(MixState::Linked),
+            (_, Some(false)) => Some(MixState::Unlinked),
+            _ => None,
+        }
+    }
+}
+
+/// Live routing state, keyed by (mix, source)
+#[derive(Debug, Clone, Default)]
+pub struct RoutingMatrix {
+    cells: HashMap<(MixOutput, Source), Cell>,
+}
+
+impl RoutingMatrix {
+    pub fn get(&self, mix: MixOutput, source: Source) -> Option<MixState> {
+        self.cells.get(&(mix, source)).and_then(Cell::state)
+    }
+
+    /// Record an injected mix action
+    pub fn apply(&mut self, mix: MixOutput, source: Source, action: MixAction) {
+        let cell = self.cells.entry((mix, source)).or_default();
+        match action {
+            MixAction::Link => {
+                cell.disabled = Some(false);
+                cell.linked = Some(true);
+            }
+            MixAction::Unlink => cell.linked = Some(false),
+            MixAction::Disable => cell.disabled = Some(true),
+            MixAction::Enable => cell.disabled = Some(false),
+        }
+    }
+
+    /// Rebuild from a snapshot (after a handover); unknown cells stay unknown
+    pub fn from_snapshot(snapshot: &MatrixSnapshot) -> Self {
+        let mut matrix = RoutingMatrix::default();
+        for (&mix, sources) in &snapshot.mixes {
+            for (&source, state) in sources {
+                let action = match state {
+                    Some(MixState::Linked) => MixAction::Link,
+                    Some(MixState::Unlinked) => MixAction::Unlink,
+                    Some(MixState::Disabled) => MixAction::Disable,
+                    None => continue,
+                };
+                matrix.apply(mix, source, action);
+            }
+        }
+        matrix
+    }
+
+    pub fn snapshot(&self) -> MatrixSnapshot {
+        let mixes = MixOutput::ALL.into_iter()
+            .map(|mix| (mix, Source::ALL.into_iter().map(|src| (src, self.get(mix, src))).collect()))
+            .collect();
+        MatrixSnapshot { mixes }
+    }
+
+    /// Known cells of one bus as bus routes, plus the sources not seen yet
+    pub fn bus_routes(&self, mix: MixOutput) -> (BTreeMap<String, MixState>, Vec<Source>) {
+        let mut routes = BTreeMap::new();
+        let mut unknown = Vec::new();
+        for source in Source::ALL {
+            match self.get(mix, source) {
+                Some(state) => { routes.insert(source.to_string(), state); }
+                None => unknown.push(source),
+            }
+        }
+        (routes, unknown)
+    }
+
+    /// Sources seen on any bus, i.e. the ones this device actually has
+    pub fn observed_sources(&self) -> Vec<Source> {
+        Source::ALL.into_iter()
+            .filter(|&src| MixOutput::ALL.into_iter().any(|mix| self.get(mix, src).is_some()))
+            .collect()
+    }
+
+    /// Update from a frame payload; returns the cell if it was a mix frame
+    pub fn observe(&mut self, payload: &[u8], callme_base: Option<u8>) -> Option<(MixOutput, Source)> {
+        let message = Message::parse(payload)?;
+        let (mix, source) = cell_for_prefix(message.prefix, callme_base)?;
+        let cell = self.cells.entry((mix, source)).or_default();
+        match message.property {
+            "mixLink" => cell.linked = Some(protocol::MixLink::decode_payload(payload)?.linked),
+            "mixDisabled" => cell.disabled = Some(protocol::MixDisabled::decode_payload(payload)?.disabled),
+            _ => return None,
+        }
+        Some((mix, source))
+    }
+}
+
+/// Whole matrix as returned for `Command::Matrix`; `None` = not seen yet
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MatrixSnapshot {
+    pub mixes: BTreeMap<MixOutput, BTreeMap<Source, Option<MixState>>>,
+}
+
+impl MatrixSnapshot {
+    pub fn get(&self, mix: MixOutput, source: Source) -> Option<MixState> {
+        self.mixes.get(&mix)?.get(&source).copied().flatten()
+    }
+}
+
+/// One cell changed by a routing request
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CellChange {
+    pub source: Source,
+    /// Previous state, `None` if the bridge had not seen it yet
+    pub from: Option<MixState>,
+    pub to: MixState,
+}
+
+/// Changes made to one bus
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BusResult {
+    pub mix: MixOutput,
+    #[serde(default)]
+    pub changed: Vec<CellChange>,
+    /// Cells left alone, with the reason
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub skipped: Vec<String>,
+}
+
+/// Reply to a routing request (bus, copy, swap)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RoutingReply {
+    #[serde(default)]
+    pub buses: Vec<BusResult>,
+    /// Problems found that the request did not fix (e.g. feedback loops)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<CommandWarning>,
+    /// Set when the request was refused; nothing was sent
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<CommandError>,
+}
+
+/// Planned changes for one bus
+#[derive(Debug, Clone)]
+pub struct BusPlan {
+    pub result: BusResult,
+    pub actions: Vec<(Source, MixAction)>,
+    pub warnings: Vec<CommandWarning>,
+}
+
+/// Expand `{source: state, "*": state}` into the desired state per source.
+/// Explicit entries win over the `*` wildcard; unmentioned sources are left alone.
+pub fn expand_bus(routes: &BTreeMap<String, MixState>) -> Result<Vec<(Source, MixState)>, CommandError> {
+    let mut explicit = Vec::new();
+    let mut wildcard = None;
+    for (name, state) in routes {
+        if name == "*" {
+            wildcard = Some(*state);
+        } else {
+            let source = name.parse::<Source>()
+                .map_err(|_| CommandError::UnknownSource { name: name.clone() })?;
+            explicit.push((source, *state));
+        }
+    }
+
+    Ok(Source::ALL.iter().filter_map(|&src| {
+        explicit.iter().find(|(s, _)| *s == src).map(|(_, st)| *st)
+            .or(wildcard)
+            .map(|st| (src, st))
+    }).collect())
+}
+
+/// Mix actions that move a cell from `from` to `to`
+pub fn actions_for(source: Source, from: Option<MixState>, to: MixState) -> Vec<MixAction> {
+    match to {
+        MixState::Linked => vec![MixAction::Link], // Link also enables
+        MixState::Disabled => vec![MixAction::Disable],
+        // CallMe cells cannot be disabled, so there is nothing to enable
+        MixState::Unlinked if source.is_callme() || from == Some(MixState::Linked) => vec![MixAction::Unlink],
+        MixState::Unlinked => vec![MixAction::Enable, MixAction::Unlink],
+    }
+}
+
+/// Work out which cells of `mix` differ from the requested routing and the
+/// actions that fix them. Cells already in the requested state are left alone;
+/// cells the bridge has not seen yet are always sent.
+///
+/// Cells that cannot be set are skipped: CallMe disables and feedback loops.
+/// `Command::validate` rejects those when they are listed explicitly, so here
+/// they come from `*` or a copied bus.
+pub fn plan_bus(
+    matrix: &RoutingMatrix,
+    mix: MixOutput,
+    routes: &BTreeMap<String, MixState>,
+) -> Result<BusPlan, CommandError> {
+    let mut result = BusResult { mix, changed: Vec::new(), skipped: Vec::new() };
+    let mut actions = Vec::new();
+    let mut warnings = Vec::new();
+    for (source, to) in expand_bus(routes)? {
+        if source.is_callme() && to == MixState::Disabled {
+            result.skipped.push(format!("{}: disable is not supported for CallMe sources", source));
+            continue;
+        }
+        let warning = match commands::check_route(mix, source, to) {
+            Ok(warning) => warning,
+            Err(e) => {
+                result.skipped.push(format!("{}: {}", source, e));
+                continue;
+            }
+        };
+        let from = matrix.get(mix, source);
+        if from == Some(to) {
+            continue;
+        }
+        warnings.extend(warning);
+        actions.extend(actions_for(source, from, to).into_iter().map(|a| (source, a)));
+        result.changed.push(CellChange { source, from, to });
+    }
+    Ok(BusPlan { result, actions, warnings })
+}
+
+/// Make `to` route like `from`. Cells whose state in `from` is unknown are
+/// left alone, and reported if `to` has a known state for them.
+pub fn plan_copy(matrix: &RoutingMatrix, from: MixOutput, to: MixOutput) -> Result<BusPlan, CommandError> {
+    let (routes, unknown) = matrix.bus_routes(from);
+    let mut plan = plan_bus(matrix, to, &routes)?;
+    plan.result.skipped.extend(unknown.iter()
+        .filter(|&&src| matrix.get(to, src).is_some())
+        .map(|src| format!("{}: state in {} unknown", src, from)));
+    Ok(plan)
+}
+
+/// Exchange the routing of two buses, both planned from the same snapshot
+pub fn plan_swap(matrix: &RoutingMatrix, first: MixOutput, second: MixOutput) -> Result<Vec<BusPlan>, CommandError> {
+    Ok(vec![plan_copy(matrix, second, first)?, plan_copy(matrix, first, second)?])
+}
+
+/// Bus that carries the program back to a remote source, if it has one
+pub fn return_bus(source: Source) -> Option<MixOutput> {
+    match source {
+        Source::Chat => Some(MixOutput::Chat),
+        Source::Usb2 => Some(MixOutput::Usb2),
+        Source::CallMe1 => Some(MixOutput::CallMe1),
+        Source::CallMe2 => Some(MixOutput::CallMe2),
+        Source::CallMe3 => Some(MixOutput::CallMe3),
+        _ => None,
+    }
+}
+
+/// Configure each remote's return bus as a mix-minus: every other observed
+/// source linked, the remote itself disabled. Sources the bridge has not seen
+/// on any bus (mono combos of a stereo pair, unused pairs) are left alone. Also returns warnings for every source
+/// that can still hear itself afterwards, on any bus.
+///
+/// CallMe sources cannot be disabled, so a CallMe remote's own cell is left
+/// alone and only warned about.
+pub fn plan_mix_minus(matrix: &RoutingMatrix, remotes: &[Source]) -> Result<(Vec<BusPlan>, Vec<CommandWarning>), CommandError> {
+    let sources = matrix.observed_sources();
+    let mut plans = Vec::new();
+    for &remote in remotes {
+        let bus = return_bus(remote)
+            .ok_or(CommandError::NoReturnBus { source: remote })?;
+        let mut routes: BTreeMap<String, MixState> = sources.iter()
+            .filter(|&&src| src != remote)
+            .map(|src| (src.to_string(), MixState::Linked))
+            .collect();
+        if !remote.is_callme() {
+            routes.insert(remote.to_string(), MixState::Disabled);
+        }
+        plans.push(plan_bus(matrix, bus, &routes)?);
+    }
+
+    let mut warnings = Vec::new();
+    for mix in MixOutput::ALL {
+        for source in Source::ALL.into_iter().filter(|&src| commands::is_feedback_loop(mix, src)) {
+            let configured = remotes.contains(&source) && return_bus(source) == Some(mix);
+            match matrix.get(mix, source) {
+                _ if configured && !source.is_callme() => {}
+                Some(MixState::Disabled) => {}
+                Some(state) => warnings.push(CommandWarning::FeedbackLoop { mix, source, state }),
+                None if configured => warnings.push(CommandWarning::CallMeLoopUnchecked { mix, source }),
+                None => {}
+            }
+        }
+    }
+    Ok((plans, warnings))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::RodeCommand;
+
+    const SESSION: [u8; 4] = [0x01; 4];
+
+    fn routes(entries: &[(&str, MixState)]) -> BTreeMap<String, MixState> {
+        entries.iter().map(|(name, state)| (name.to_string(), *state)).collect()
+    }
+
+    fn prefix(mix: MixOutput, source: Source) -> u8 {
+        protocol::calculate_mix_prefix(source.to_index(), mix.to_index())
+    }
+
+    #[test]
+    fn wildcard_covers_unlisted_sources() {
+        let expanded = expand_bus(&routes(&[("*", MixState::Disabled), ("combo2", MixState::Linked)])).unwrap();
+        assert_eq!(expanded.len(), Source::ALL.len());
+        assert!(expanded.contains(&(Source::Combo2, MixState::Linked)));
+        assert!(expanded.contains(&(Source::SoundPad, MixState::Disabled)));
+
+        // Without `*` only listed sources are touched
+        let expanded = expand_bus(&routes(&[("pad", MixState::Unlinked)])).unwrap();
+        assert_eq!(expanded, [(Source::SoundPad, MixState::Unlinked)]);
+
+        assert_eq!(
+            expand_bus(&routes(&[("nope", MixState::Linked)])),
+            Err(CommandError::UnknownSource { name: "nope".to_string() })
+        );
+    }
+
+    #[test]
+    fn only_differing_cells_are_sent() {
+        let mut matrix = RoutingMatrix::default();
+        matrix.apply(MixOutput::Headphone2, Source::Combo1, MixAction::Link);
+        matrix.apply(MixOutput::Headphone2, Source::Combo2, MixAction::Disable);
+
+        let plan = plan_bus(&matrix, MixOutput::Headphone2, &routes(&[
+            ("combo1", MixState::Linked),
+            ("combo2", MixState::Linked),
+        ])).unwrap();
+        assert_eq!(plan.actions, [(Source::Combo2, MixAction::Link)]);
+        assert_eq!(plan.result.changed, [CellChange { source: Source::Combo2, from: Some(MixState::Disabled), to: MixState::Linked }]);
+    }
+
+    #[test]
+    fn unknown_cells_are_always_sent() {
+        let plan = plan_bus(&RoutingMatrix::default(), MixOutput::Headphone1, &routes(&[("pad", MixState::Unlinked)])).unwrap();
+        // Unknown might be disabled, so enable before unlinking
+        assert_eq!(plan.actions, [(Source::SoundPad, MixAction::Enable), (Source::SoundPad, MixAction::Unlink)]);
+        assert_eq!(plan.result.changed, [CellChange { source: Source::SoundPad, from: None, to: MixState::Unlinked }]);
+    }
+
+    #[test]
+    fn action_order() {
+        assert_eq!(actions_for(Source::Combo1, Some(MixState::Disabled), MixState::Linked), [MixAction::Link]);
+        assert_eq!(actions_for(Source::Combo1, Some(MixState::Disabled), MixState::Unlinked), [MixAction::Enable, MixAction::Unlink]);
+        assert_eq!(actions_for(Source::Combo1, Some(MixState::Linked), MixState::Unlinked), [MixAction::Unlink]);
+        assert_eq!(actions_for(Source::CallMe1, None, MixState::Unlinked), [MixAction::Unlink]);
+        assert_eq!(actions_for(Source::Combo1, None, MixState::Disabled), [MixAction::Disable]);
+    }
+
+    #[test]
+    fn wildcard_skips_cells_that_cannot_be_set() {
+        let plan = plan_bus(&RoutingMatrix::default(), MixOutput::Usb1, &routes(&[("*", MixState::Linked)])).unwrap();
+        assert!(!plan.actions.iter().any(|(src, _)| *src == Source::Usb1));
+        assert_eq!(plan.result.skipped.len(), 1);
+        assert!(plan.warnings.contains(&CommandWarning::SharedDeviceEcho { mix: MixOutput::Usb1, source: Source::Chat }));
+    }
+
+    fn snapshot(cells: &[(MixOutput, Source, MixState)]) -> RoutingMatrix {
+        let mut snapshot = MatrixSnapshot::default();
+        for &(mix, source, state) in cells {
+            snapshot.mixes.entry(mix).or_default().insert(source, Some(state));
+        }
+        RoutingMatrix::from_snapshot(&snapshot)
+    }
+
+    #[test]
+    fn copy_leaves_cells_unknown_in_the_source_bus() {
+        let (hp1, hp2) = (MixOutput::Headphone1, MixOutput::Headphone2);
+        let matrix = snapshot(&[
+            (hp1, Source::Combo1, MixState::Linked),
+            (hp2, Source::Combo1, MixState::Unlinked),
+            (hp2, Source::SoundPad, MixState::Linked),
+        ]);
+        let plan = plan_copy(&matrix, hp1, hp2).unwrap();
+        assert_eq!(plan.result.mix, hp2);
+        assert_eq!(plan.actions, [(Source::Combo1, MixAction::Link)]);
+        assert_eq!(plan.result.skipped, [format!("{}: state in {} unknown", Source::SoundPad, hp1)]);
+    }
+
+    #[test]
+    fn swap_exchanges_both_buses() {
+        let (hp1, hp2) = (MixOutput::Headphone1, MixOutput::Headphone2);
+        let matrix = snapshot(&[
+            (hp1, Source::Combo1, MixState::Linked),
+            (hp1, Source::SoundPad, MixState::Disabled),
+            (hp1, Source::Bluetooth, MixState::Unlinked),
+            (hp1, Source::CallMe1, MixState::Linked),
+            (hp2, Source::Combo1, MixState::Unlinked),
+            (hp2, Source::SoundPad, MixState::Linked),
+            (hp2, Source::CallMe1, MixState::Disabled),
+        ]);
+        let plans = plan_swap(&matrix, hp1, hp2).unwrap();
+        let [into_first, into_second] = plans.as_slice() else { panic!("expected two plans") };
+
+        assert_eq!(into_first.result.mix, hp1);
+        assert_eq!(into_first.actions, [
+            (Source::Combo1, MixAction::Unlink),
+            (Source::SoundPad, MixAction::Link),
+        ]);
+        // Bluetooth is unknown on the second bus, and CallMe cannot be disabled
+        assert_eq!(into_first.result.skipped, [
+            format!("{}: disable is not supported for CallMe sources", Source::CallMe1),
+            format!("{}: state in {} unknown", Source::Bluetooth, hp2),
+        ]);
+
+        assert_eq!(into_second.result.mix, hp2);
+        assert_eq!(into_second.actions, [
+            (Source::Combo1, MixAction::Link),
+            (Source::Bluetooth, MixAction::Enable),
+            (Source::Bluetooth, MixAction::Unlink),
+            (Source::SoundPad, MixAction::Disable),
+            (Source::CallMe1, MixAction::Link),
+        ]);
+        assert!(into_second.result.skipped.is_empty());
+        assert!(into_second.result.changed.contains(&CellChange { source: Source::Bluetooth, from: None, to: MixState::Unlinked }));
+    }
+
+    #[test]
+    fn mix_minus_links_observed_sources_only() {
+        let matrix = snapshot(&[
+            (MixOutput::Headphone1, Source::Combo1, MixState::Linked),
+            (MixOutput::Chat, Source::SoundPad, MixState::Linked),
+            (MixOutput::Chat, Source::Chat, MixState::Linked),
+            (MixOutput::Usb2, Source::Usb2, MixState::Unlinked),
+        ]);
+        let (plans, warnings) = plan_mix_minus(&matrix, &[Source::Chat, Source::CallMe1]).unwrap();
+        let [chat, callme] = plans.as_slice() else { panic!("expected two plans") };
+
+        assert_eq!(chat.result.mix, MixOutput::Chat);
+        let mut actions = chat.actions.clone();
+        actions.sort_by_key(|&(src, _)| src);
+        assert_eq!(actions, [
+            (Source::Combo1, MixAction::Link),
+            (Source::Chat, MixAction::Disable),
+            (Source::Usb2, MixAction::Link),
+        ]);
+
+        // A CallMe remote keeps its own cell; the rest is linked as for chat
+        assert_eq!(callme.result.mix, MixOutput::CallMe1);
+        let mut sources: Vec<_> = callme.actions.iter().map(|&(src, _)| src).collect();
+        sources.sort();
+        assert_eq!(sources, [Source::Combo1, Source::Chat, Source::Usb2, Source::SoundPad]);
+        assert!(callme.actions.iter().all(|&(_, action)| action == MixAction::Link));
+
+        assert_eq!(warnings, [
+            CommandWarning::FeedbackLoop { mix: MixOutput::Usb2, source: Source::Usb2, state: MixState::Unlinked },
+            CommandWarning::CallMeLoopUnchecked { mix: MixOutput::CallMe1, source: Source::CallMe1 },
+        ]);
+    }
+
+    #[test]
+    fn mix_minus_needs_a_return_bus() {
+        assert_eq!(
+            plan_mix_minus(&RoutingMatrix::default(), &[Source::Combo1]).unwrap_err(),
+            CommandError::NoReturnBus { source: Source::Combo1 }
+        );
+    }
+
+    #[test]
+    fn apply_tracks_link_and_disable_separately() {
+        let mut matrix = RoutingMatrix::default();
+        let (mix, src) = (MixOutput::Speaker, Source::Bluetooth);
+        assert_eq!(matrix.get(mix, src), None);
+        matrix.apply(mix, src, MixAction::Unlink);
+        assert_eq!(matrix.get(mix, src), Some(MixState::Unlinked));
+        matrix.apply(mix, src, MixAction::Disable);
+        assert_eq!(matrix.get(mix, src), Some(MixState::Disabled));
+        // Enable keeps the link mode
+        matrix.apply(mix, src, MixAction::Enable);
+        assert_eq!(matrix.get(mix, src), Some(MixState::Unlinked));
+        matrix.apply(mix, src, MixAction::Link);
+        assert_eq!(matrix.get(mix, src), Some(MixState::Linked));
+    }
+
+    #[test]
+    fn observe_mixer_frames() {
+        let mut matrix = RoutingMatrix::default();
+        let (mix, src) = (MixOutput::Headphone1, Source::SoundPad);
+        let link = protocol::MixLink { prefix: prefix(mix, src), linked: false }.build_payload(&SESSION);
+        assert_eq!(matrix.observe(&link, None), Some((mix, src)));
+        assert_eq!(matrix.get(mix, src), Some(MixState::Unlinked));
+
+        let disabled = protocol::MixDisabled { prefix: prefix(mix, src), disabled: true }.build_payload(&SESSION);
+        matrix.observe(&disabled, None);
+        assert_eq!(matrix.get(mix, src), Some(MixState::Disabled));
+
+        // Mute is not part of the routing state
+        let mute = protocol::MixMute { prefix: prefix(mix, src), muted: true }.build_payload(&SESSION);
+        assert_eq!(matrix.observe(&mute, None), None);
+    }
+
+    #[test]
+    fn callme_cells_need_the_prefix_base() {
+        let payload = protocol::CallMeLinkRequest {
+            prefix: [protocol::CALLME_PREFIX_BASE + MixOutput::Headphone1.to_index(), 2],
+            flags: protocol::MixRequestFlags::REQUEST,
+        }.build_payload(&SESSION);
+        let message = Message::parse(&payload).unwrap();
+        assert_eq!(cell_for_prefix(message.prefix, None), None);
+        assert_eq!(
+            cell_for_prefix(message.prefix, Some(protocol::CALLME_PREFIX_BASE)),
+            Some((MixOutput::Headphone1, Source::CallMe2))
+        );
+    }
+}
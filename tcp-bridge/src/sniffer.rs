@@ -1,7 +1,9 @@
-pub fn print_hexdump(label: &str, data: &[u8]) {
+use tcp_bridge::config::LoggingConfig;
+
+pub fn print_hexdump(label: &str, data: &[u8], max_bytes: usize) {
     println!("{}: ({} bytes)", label, data.len());
     let len = data.len();
-    let display_len = if len > 128 { 128 } else { len };
+    let display_len = len.min(max_bytes);
     
     for (i, chunk) in data[..display_len].chunks(16).enumerate() {
         print!("{:08x}  ", i * 16);
@@ -25,12 +27,13 @@ pub fn print_hexdump(label: &str, data: &[u8]) {
         }
         println!("|");
     }
-    if len > 128 {
-        println!("... ({} bytes truncated)", len - 128);
+    if len > display_len {
+        println!("... ({} bytes truncated)", len - display_len);
     }
     println!();
 }
 
+/// Taking `logging` per frame lets a config reload apply mid-connection
 pub struct SnifferState {
     pub last_packet: Option<(String, Vec<u8>)>,
     pub repeat_count: usize,
@@ -44,7 +47,18 @@ impl SnifferState {
         }
     }
 
-    pub fn handle_packet(&mut self, direction: &str, data: &[u8]) {
+    /// Hexdump a single frame, e.g. an injected one
+    pub fn dump(&self, label: &str, data: &[u8], logging: &LoggingConfig) {
+        if logging.hexdump {
+            print_hexdump(label, data, logging.hexdump_bytes);
+        }
+    }
+
+    /// Nothing is printed while `logging.hexdump` is off
+    pub fn handle_packet(&mut self, direction: &str, data: &[u8], logging: &LoggingConfig) {
+        if !logging.hexdump {
+            return;
+        }
         let current = (direction.to_string(), data.to_vec());
         if let Some((last_dir, last_data)) = &self.last_packet {
             if *last_dir == current.0 && *last_data == current.1 {
@@ -53,12 +67,12 @@ impl SnifferState {
                 if self.repeat_count > 0 {
                     println!("(Previous packet repeated {} times)", self.repeat_count);
                 }
-                print_hexdump(direction, data);
+                print_hexdump(direction, data, logging.hexdump_bytes);
                 self.last_packet = Some(current);
                 self.repeat_count = 0;
             }
         } else {
-             print_hexdump(direction, data);
+             print_hexdump(direction, data, logging.hexdump_bytes);
              self.last_packet = Some(current);
              self.repeat_count = 0;
         }
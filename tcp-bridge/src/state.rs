@@ -0,0 +1,274 @@
+//! Proxy state shared between connections and the control socket.
+
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tcp_bridge::commands::CommandError;
+use tcp_bridge::config::Config;
+use tcp_bridge::faders::FaderBank;
+use tcp_bridge::names::MixOutput;
+use tcp_bridge::routing::RoutingMatrix;
+use tcp_bridge::protocol::{self, CallMeChannel, CallMeLayout, CommandSpecs};
+use tcp_bridge::status::{BridgeStatus, PingTracker, SessionEvent};
+use tcp_bridge::traffic::{Direction, TrafficLog};
+
+use tcp_bridge::inject::ProxyCommand;
+
+use crate::handover::SessionState;
+
+pub type SharedState = Arc<Mutex<ProxyState>>;
+
+pub struct ProxyState {
+    pub connections: usize,
+    pub session_id: Option<Vec<u8>>,
+    /// Session ID of the CallMe channel, learned from CallMe mix frames
+    pub callme_session: Option<Vec<u8>>,
+    /// CallMe prefix layout, learned from the same frames
+    pub callme_layout: CallMeLayout,
+    pub session_changes: u64,
+    pub last_session_change: Option<SessionEvent>,
+    pub ping: PingTracker,
+    /// Injected commands with the properties they still determine, in
+    /// injection order; re-applied after a session change
+    pub overrides: Vec<(Vec<String>, ProxyCommand)>,
+    /// Mix routing as reported by the mixer and set by injected commands
+    pub routing: RoutingMatrix,
+    /// Current config; replaced on reload, so read it per use
+    pub config: Arc<Config>,
+    /// Declarative commands from `proxy.spec`; replaced on reload
+    pub specs: Arc<CommandSpecs>,
+    /// Fader mute/source/level as reported by the mixer and set by injected commands
+    pub faders: FaderBank,
+    /// Recent non-ping frames in both directions
+    pub traffic: TrafficLog,
+    /// When the last frame (pings included) went to / came from the mixer
+    last_to_mixer: Option<Instant>,
+    last_from_mixer: Option<Instant>,
+}
+
+impl ProxyState {
+    pub fn shared(config: Config, specs: CommandSpecs) -> SharedState {
+        let ping_timeout = Duration::from_millis(config.proxy.ping_timeout_ms);
+        Arc::new(Mutex::new(ProxyState {
+            connections: 0,
+            session_id: None,
+            callme_session: None,
+            callme_layout: CallMeLayout::default(),
+            session_changes: 0,
+            last_session_change: None,
+            ping: PingTracker::new(ping_timeout),
+            overrides: Vec::new(),
+            routing: RoutingMatrix::default(),
+            config: Arc::new(config),
+            specs: Arc::new(specs),
+            faders: FaderBank::default(),
+            traffic: TrafficLog::default(),
+            last_to_mixer: None,
+            last_from_mixer: None,
+        }))
+    }
+
+    /// The first GUI connection starts a fresh ping exchange; further ones
+    /// (e.g. a probe) keep the health state of the running one
+    pub fn connection_opened(&mut self) {
+        if self.connections == 0 {
+            self.ping = PingTracker::new(self.ping_timeout());
+        }
+        self.connections += 1;
+    }
+
+    pub fn connection_closed(&mut self) {
+        self.connections = self.connections.saturating_sub(1);
+    }
+
+    /// Record a sniffed session ID, returning an event if it changed
+    pub fn observe_session(&mut self, sid: &[u8]) -> Option<SessionEvent> {
+        if self.session_id.as_deref() == Some(sid) {
+            return None;
+        }
+        let old = self.session_id.replace(sid.to_vec());
+        let event = SessionEvent { old: old.map(hex::encode), new: hex::encode(sid) };
+        if event.old.is_some() {
+            self.session_changes += 1;
+            self.last_session_change = Some(event.clone());
+        }
+        Some(event)
+    }
+
+    /// Record the CallMe channel's session; returns true if it is new
+    pub fn observe_callme_session(&mut self, sid: &[u8]) -> bool {
+        if self.callme_session.as_deref() == Some(sid) {
+            return false;
+        }
+        self.callme_session = Some(sid.to_vec());
+        true
+    }
+
+    /// Session and prefix base for CallMe mix frames, once both are known
+    pub fn callme_channel(&self) -> Option<CallMeChannel> {
+        Some(CallMeChannel {
+            session: self.callme_session.clone()?,
+            prefix_base: self.callme_layout.prefix_base()?,
+        })
+    }
+
+    /// The proxy drops injected commands without a GUI connection or a session to use
+    pub fn ready_to_inject(&self) -> Result<(), CommandError> {
+        if self.connections == 0 {
+            return Err(CommandError::NotConnected);
+        }
+        if self.session_id.is_none() && !self.config.features.allow_default_session {
+            return Err(CommandError::NoSession);
+        }
+        Ok(())
+    }
+
+    /// Update routing, fader state and the traffic log from a frame
+    pub fn observe(&mut self, direction: Direction, payload: &[u8]) {
+        let last = match direction {
+            Direction::ToMixer => &mut self.last_to_mixer,
+            Direction::FromMixer => &mut self.last_from_mixer,
+        };
+        *last = Some(Instant::now());
+        if !protocol::is_ping(payload) {
+            self.traffic.record(direction, payload);
+        }
+        self.routing.observe(payload, self.callme_layout.prefix_base());
+        self.faders.observe(payload);
+    }
+
+    /// Track an injected command's effect on routing and faders and remember it for re-apply
+    pub fn record_injected(&mut self, cmd: &ProxyCommand) {
+        match cmd {
+            ProxyCommand::Batch(cmds) => {
+                for cmd in cmds {
+                    self.record_injected(cmd);
+                }
+                return;
+            }
+            ProxyCommand::Mix { action, mix_index, source } => {
+                if let Some(mix) = MixOutput::from_index(*mix_index) {
+                    self.routing.apply(mix, *source, *action);
+                }
+            }
+            ProxyCommand::Mute { fader_index, mute } => self.faders.set_muted(*fader_index, *mute),
+            ProxyCommand::Source { fader_index, source_id } => self.faders.set_source(*fader_index, *source_id),
+            ProxyCommand::Level { fader_index, level } => self.faders.set_level(*fader_index, *level),
+            _ => {}
+        }
+        self.record_override(cmd);
+    }
+
+    /// Remember an injected command, replacing older ones for the same target
+    pub fn record_override(&mut self, cmd: &ProxyCommand) {
+        let new_keys = cmd.target_keys();
+        if new_keys.is_empty() {
+            return;
+        }
+        self.overrides.retain_mut(|(keys, _)| {
+            keys.retain(|k| !new_keys.contains(k));
+            !keys.is_empty()
+        });
+        self.overrides.push((new_keys, cmd.clone()));
+    }
+
+    /// Switch to a reloaded config and spec file. Connections pick them up
+    /// with their next frame.
+    pub fn reconfigure(&mut self, config: Config, specs: CommandSpecs) {
+        self.config = Arc::new(config);
+        self.specs = Arc::new(specs);
+        self.ping.set_timeout(self.ping_timeout());
+    }
+
+    fn ping_timeout(&self) -> Duration {
+        Duration::from_millis(self.config.proxy.ping_timeout_ms)
+    }
+
+    /// What a new process needs to carry on (see `handover`)
+    pub fn session_state(&self) -> SessionState {
+        SessionState {
+            session_id: self.session_id.as_ref().map(hex::encode),
+            callme_session: self.callme_session.as_ref().map(hex::encode),
+            callme_layout: self.callme_layout,
+            session_changes: self.session_changes,
+            last_session_change: self.last_session_change.clone(),
+            routing: self.routing.snapshot(),
+            faders: self.faders.snapshot(),
+            overrides: self.overrides.clone(),
+        }
+    }
+
+    /// Take over the state of the previous process
+    pub fn restore(&mut self, handed_over: &SessionState) {
+        let decode = |id: &Option<String>| id.as_deref().and_then(|id| hex::decode(id).ok());
+        self.session_id = decode(&handed_over.session_id);
+        self.callme_session = decode(&handed_over.callme_session);
+        self.callme_layout = handed_over.callme_layout;
+        self.session_changes = handed_over.session_changes;
+        self.last_session_change = handed_over.last_session_change.clone();
+        self.routing = RoutingMatrix::from_snapshot(&handed_over.routing);
+        self.faders = FaderBank::from_snapshot(&handed_over.faders);
+        self.overrides = handed_over.overrides.clone();
+    }
+
+    pub fn status(&self) -> BridgeStatus {
+        let now = Instant::now();
+        let age = |at: Option<Instant>| at.map(|at| now.duration_since(at).as_millis() as u64);
+        BridgeStatus {
+            connections: self.connections,
+            session_id: self.session_id.as_ref().map(hex::encode),
+            callme_session_id: self.callme_session.as_ref().map(hex::encode),
+            session_changes: self.session_changes,
+            last_session_change: self.last_session_change.clone(),
+            last_to_mixer_ms: age(self.last_to_mixer),
+            last_from_mixer_ms: age(self.last_from_mixer),
+            upstream: self.ping.snapshot(now),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tcp_bridge::commands::MixAction;
+    use tcp_bridge::names::Source;
+
+    fn mix(action: MixAction) -> ProxyCommand {
+        ProxyCommand::Mix { action, mix_index: 0, source: Source::Chat }
+    }
+
+    fn replayed(state: &SharedState) -> Vec<String> {
+        state.lock().unwrap().overrides.iter().map(|(_, cmd)| format!("{:?}", cmd)).collect()
+    }
+
+    #[test]
+    fn overrides_replace_per_property() {
+        let state = ProxyState::shared(Config::default(), CommandSpecs::default());
+        {
+            let mut state = state.lock().unwrap();
+            state.record_override(&mix(MixAction::Link));
+            state.record_override(&mix(MixAction::Disable));
+        }
+        // Disable only replaces the enable part of the link
+        assert_eq!(replayed(&state), [format!("{:?}", mix(MixAction::Link)), format!("{:?}", mix(MixAction::Disable))]);
+
+        state.lock().unwrap().record_override(&mix(MixAction::Unlink));
+        assert_eq!(replayed(&state), [format!("{:?}", mix(MixAction::Disable)), format!("{:?}", mix(MixAction::Unlink))]);
+
+        state.lock().unwrap().record_override(&mix(MixAction::Link));
+        assert_eq!(replayed(&state), [format!("{:?}", mix(MixAction::Link))]);
+    }
+
+    #[test]
+    fn overrides_survive_a_handover() {
+        let old = ProxyState::shared(Config::default(), CommandSpecs::default());
+        old.lock().unwrap().record_override(&mix(MixAction::Unlink));
+        old.lock().unwrap().record_override(&ProxyCommand::Mute { fader_index: 2, mute: true });
+        let json = serde_json::to_string(&old.lock().unwrap().session_state()).unwrap();
+
+        let new = ProxyState::shared(Config::default(), CommandSpecs::default());
+        new.lock().unwrap().restore(&serde_json::from_str(&json).unwrap());
+        assert_eq!(replayed(&new), replayed(&old));
+        assert_eq!(new.lock().unwrap().overrides[1].0, ["mute:2"]);
+    }
+}
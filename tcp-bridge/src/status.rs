@@ -0,0 +1,211 @@
+//! Bridge status reported over the control socket.
+//!
+//! The GUI sends `ping` frames to the mixer and the mixer answers each one
+//! with the same payload.
+//! Tracking that exchange tells us whether the audio engine is still alive.
+
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Snapshot returned for `Command::Status`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct BridgeStatus {
+    /// Number of GUI connections currently proxied
+    pub connections: usize,
+    /// Last sniffed session ID (hex), if any
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+    /// Session ID of the CallMe channel (hex), if observed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub callme_session_id: Option<String>,
+    /// Number of times the session ID changed after the first sniff
+    #[serde(default)]
+    pub session_changes: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_session_change: Option<SessionEvent>,
+    /// Time since the last frame (pings included) from the GUI to the mixer
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_to_mixer_ms: Option<u64>,
+    /// Time since the last frame from the mixer to the GUI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_from_mixer_ms: Option<u64>,
+    pub upstream: UpstreamHealth,
+}
+
+/// Raised whenever the sniffed session ID differs from the known one
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionEvent {
+    /// Previous session (hex), `None` for the first sniff
+    pub old: Option<String>,
+    pub new: String,
+}
+
+/// Health of the mixer connection, derived from the ping exchange
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UpstreamHealth {
+    pub healthy: bool,
+    pub pings_sent: u64,
+    pub pings_answered: u64,
+    /// Time between the last ping and its reply
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// Time between the last two pings from the GUI
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ping_gap_ms: Option<u64>,
+    /// How long the oldest unanswered ping has been waiting
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub waiting_ms: Option<u64>,
+}
+
+impl Default for UpstreamHealth {
+    fn default() -> Self {
+        UpstreamHealth {
+            healthy: true,
+            pings_sent: 0,
+            pings_answered: 0,
+            latency_ms: None,
+            ping_gap_ms: None,
+            waiting_ms: None,
+        }
+    }
+}
+
+/// Health transition reported by `PingTracker::check`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthChange {
+    Unhealthy { waiting: Duration },
+    Recovered { latency: Duration },
+}
+
+/// Follows the ping exchange of one proxied connection
+#[derive(Debug)]
+pub struct PingTracker {
+    timeout: Duration,
+    /// Oldest ping not answered yet
+    unanswered_since: Option<Instant>,
+    last_ping: Option<Instant>,
+    latency: Option<Duration>,
+    gap: Option<Duration>,
+    pings_sent: u64,
+    pings_answered: u64,
+    healthy: bool,
+}
+
+impl PingTracker {
+    pub fn new(timeout: Duration) -> Self {
+        PingTracker {
+            timeout,
+            unanswered_since: None,
+            last_ping: None,
+            latency: None,
+            gap: None,
+            pings_sent: 0,
+            pings_answered: 0,
+            healthy: true,
+        }
+    }
+
+    /// Applies to the ping currently waiting, too
+    pub fn set_timeout(&mut self, timeout: Duration) {
+        self.timeout = timeout;
+    }
+
+    /// GUI -> mixer ping
+    pub fn on_ping(&mut self, now: Instant) {
+        if let Some(last) = self.last_ping {
+            self.gap = Some(now.duration_since(last));
+        }
+        self.last_ping = Some(now);
+        self.pings_sent += 1;
+        // Keep the oldest unanswered ping so a hung mixer is detected
+        self.unanswered_since.get_or_insert(now);
+    }
+
+    /// Mixer -> GUI ping reply. Replies look the same, so one answers every
+    /// outstanding ping and the latency is measured from the latest.
+    pub fn on_reply(&mut self, now: Instant) -> Option<HealthChange> {
+        self.unanswered_since.take()?;
+        let latency = now.duration_since(self.last_ping?);
+        self.latency = Some(latency);
+        self.pings_answered += 1;
+        if !self.healthy {
+            self.healthy = true;
+            return Some(HealthChange::Recovered { latency });
+        }
+        None
+    }
+
+    /// Flag the connection once a ping has waited longer than the timeout
+    pub fn check(&mut self, now: Instant) -> Option<HealthChange> {
+        let waiting = now.duration_since(self.unanswered_since?);
+        if self.healthy && waiting > self.timeout {
+            self.healthy = false;
+            return Some(HealthChange::Unhealthy { waiting });
+        }
+        None
+    }
+
+    pub fn snapshot(&self, now: Instant) -> UpstreamHealth {
+        UpstreamHealth {
+            healthy: self.healthy,
+            pings_sent: self.pings_sent,
+            pings_answered: self.pings_answered,
+            latency_ms: self.latency.map(|d| d.as_millis() as u64),
+            ping_gap_ms: self.gap.map(|d| d.as_millis() as u64),
+            waiting_ms: self.unanswered_since.map(|t| now.duration_since(t).as_millis() as u64),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::is_ping;
+
+    #[test]
+    fn only_ping_is_a_keepalive() {
+        assert!(is_ping(b"ping"));
+        assert!(!is_ping(b"pong"));
+        assert!(!is_ping(&[0x01, 0x01, 0x01, 0x01, 0x1c]));
+    }
+
+    #[test]
+    fn answer_without_ping_is_ignored() {
+        let start = Instant::now();
+        let mut tracker = PingTracker::new(Duration::from_secs(5));
+        assert_eq!(tracker.on_reply(start), None);
+        assert_eq!(tracker.snapshot(start).pings_answered, 0);
+    }
+
+    #[test]
+    fn unanswered_ping_flags_and_answer_recovers() {
+        let start = Instant::now();
+        let mut tracker = PingTracker::new(Duration::from_secs(5));
+        tracker.on_ping(start);
+        assert_eq!(tracker.check(start + Duration::from_secs(4)), None);
+        let late = start + Duration::from_secs(6);
+        assert_eq!(tracker.check(late), Some(HealthChange::Unhealthy { waiting: Duration::from_secs(6) }));
+        assert_eq!(tracker.on_reply(late), Some(HealthChange::Recovered { latency: Duration::from_secs(6) }));
+        assert_eq!(tracker.snapshot(late).latency_ms, Some(6000));
+    }
+
+    /// Pings the mixer answers late do not add up into the latency
+    #[test]
+    fn latency_is_measured_from_the_latest_ping() {
+        let start = Instant::now();
+        let at = |ms| start + Duration::from_millis(ms);
+        let mut tracker = PingTracker::new(Duration::from_secs(5));
+        tracker.on_ping(at(0));
+        tracker.on_ping(at(1000));
+        tracker.on_ping(at(2000));
+        assert_eq!(tracker.snapshot(at(2500)).waiting_ms, Some(2500));
+
+        assert_eq!(tracker.on_reply(at(2040)), None);
+        let health = tracker.snapshot(at(2500));
+        assert_eq!((health.latency_ms, health.waiting_ms), (Some(40), None));
+        assert_eq!((health.pings_sent, health.pings_answered, health.ping_gap_ms), (3, 1, Some(1000)));
+        // A second reply to the same pings is not counted
+        assert_eq!(tracker.on_reply(at(2050)), None);
+        assert_eq!(tracker.snapshot(at(2500)).pings_answered, 1);
+    }
+}
@@ -0,0 +1,189 @@
+//! `--setup-network`: a supervising parent that owns the network setup.
+//!
+//! The parent adds the loopback alias, runs the proxy as a child process,
+//! installs the DNAT rule once the child listens and restarts the GUI so
+//! it reconnects through the proxy. However the child ends (exit, panic,
+//! signal, SIGKILL), the parent removes what it added. Signals sent to the
+//! parent are forwarded to the child. Only a SIGKILL of the parent skips
+//! the teardown; the next start removes leftover rules.
+//!
+//! With `--handover` the new parent adopts the setup of the running one
+//! (rule and aliases are removed when it ends) and leaves the GUI alone;
+//! the old parent sees its child exit with `EXIT_HANDED_OVER` and skips
+//! its teardown.
+
+use std::net::Ipv4Addr;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use tokio::signal::unix::{SignalKind, signal};
+
+use tcp_bridge::network::{self, GUI_PROCESS, NetworkSetup};
+
+use crate::handover::EXIT_HANDED_OVER;
+
+/// Set in the child's environment so it runs the proxy itself
+pub const SUPERVISED_ENV: &str = "TCP_BRIDGE_SUPERVISED";
+
+/// How long the child may take to start listening
+const READY_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Leftover copies of the rule removed at startup, at most
+const MAX_STALE_RULES: usize = 16;
+
+/// What this process added and has to remove again
+struct Applied {
+    network: NetworkSetup,
+    aliases: Vec<Ipv4Addr>,
+    rule: bool,
+}
+
+impl Applied {
+    fn teardown(&mut self) {
+        if std::mem::take(&mut self.rule) {
+            run_logged(&self.network.iptables("-D"));
+        }
+        for ip in self.aliases.drain(..) {
+            run_logged(&network::alias_command("del", ip));
+        }
+    }
+}
+
+/// Also covers a panic in the parent
+impl Drop for Applied {
+    fn drop(&mut self) {
+        self.teardown();
+    }
+}
+
+/// `--print-setup`: the commands `--setup-network` runs, without running them
+pub fn print_setup(net: &NetworkSetup) {
+    println!("# Setup (aliases only if missing)");
+    for ip in net.aliases() {
+        println!("{}", network::shell(&network::alias_command("add", ip)));
+    }
+    println!("{}  # repeated while it succeeds, removes leftovers", network::shell(&net.iptables("-D")));
+    println!("# ... start the proxy and wait until {}:{} listens ...", net.bind_ip, net.bind_port);
+    println!("{}", network::shell(&net.iptables("-A")));
+    println!("pkill {}", GUI_PROCESS);
+    println!("# Teardown (when the proxy exits, crashes or is killed)");
+    println!("{}", network::shell(&net.iptables("-D")));
+    for ip in net.aliases() {
+        println!("{}  # only if it was added", network::shell(&network::alias_command("del", ip)));
+    }
+}
+
+/// Set up the network, run the proxy as a child and tear down when it ends.
+/// Returns the child's exit code.
+pub async fn supervise(net: NetworkSetup, handover: bool) -> Result<i32, Box<dyn std::error::Error>> {
+    let mut applied = Applied { network: net.clone(), aliases: Vec::new(), rule: false };
+    let mut existing = Vec::new();
+    for ip in net.aliases() {
+        if network::has_alias(ip)? {
+            existing.push(ip);
+        } else {
+            network::run(&network::alias_command("add", ip))?;
+            println!("[Network] Added alias {} on lo", ip);
+            applied.aliases.push(ip);
+        }
+    }
+    // The running bridge's rule keeps the GUI on the proxy during a handover
+    if !handover {
+        let stale = (0..MAX_STALE_RULES).take_while(|_| network::run(&net.iptables("-D")).is_ok()).count();
+        if stale > 0 {
+            println!("[Network] Removed {} leftover DNAT rule(s)", stale);
+        }
+    }
+
+    // Registered before the child can send it; the default action would kill us
+    let mut taken_over = signal(SignalKind::user_defined1())?;
+    // Same binary and arguments; the environment tells it not to supervise
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(std::env::args_os().skip(1))
+        .env(SUPERVISED_ENV, "1")
+        .spawn()?;
+    let pid = child.id().ok_or("proxy exited immediately")? as i32;
+
+    // Ready once it listens, or after a handover once it has taken over
+    let ready = if handover { "taking over" } else { "listening" };
+    let deadline = Instant::now() + READY_TIMEOUT;
+    loop {
+        if let Some(status) = child.try_wait()? {
+            eprintln!("[Network] Proxy exited before {} ({})", ready, status);
+            return Ok(exit_code(status));
+        }
+        if !handover && network::is_listening(net.bind_ip, net.bind_port)? {
+            break;
+        }
+        if Instant::now() > deadline {
+            let _ = child.kill().await;
+            return Err(format!("proxy not {} on {}:{} after {:?}", ready, net.bind_ip, net.bind_port, READY_TIMEOUT).into());
+        }
+        tokio::select! {
+            _ = taken_over.recv(), if handover => break,
+            _ = tokio::time::sleep(Duration::from_millis(100)) => {}
+        }
+    }
+
+    if handover {
+        // What the running bridge set up is ours to remove now
+        applied.aliases.extend(existing);
+        applied.rule = net.count_rules()? > 0;
+    }
+    if applied.rule {
+        println!("[Network] Adopted the running bridge's network setup");
+    } else {
+        network::run(&net.iptables("-A"))?;
+        applied.rule = true;
+        println!("[Network] DNAT {}:{} -> {}:{} installed", net.target_ip, net.target_port, net.bind_ip, net.bind_port);
+    }
+    // Not running is fine; it connects through the proxy when it starts
+    if !handover && network::run(&["pkill", GUI_PROCESS]).is_ok() {
+        println!("[Network] Restarted {} so it reconnects through the proxy", GUI_PROCESS);
+    }
+
+    let mut sigint = signal(SignalKind::interrupt())?;
+    let mut sigterm = signal(SignalKind::terminate())?;
+    let mut sighup = signal(SignalKind::hangup())?;
+    let status = loop {
+        let signo = tokio::select! {
+            status = child.wait() => break status?,
+            _ = sigint.recv() => libc::SIGINT,
+            _ = sigterm.recv() => libc::SIGTERM,
+            _ = sighup.recv() => libc::SIGHUP,
+        };
+        // SAFETY: plain kill(2) on our own child's PID
+        unsafe { libc::kill(pid, signo) };
+    };
+
+    if status.code() == Some(EXIT_HANDED_OVER) {
+        println!("[Network] Proxy handed over, leaving the network setup to the new bridge");
+        applied.aliases.clear();
+        applied.rule = false;
+    } else {
+        println!("[Network] Proxy exited ({}), removing network setup", status);
+        applied.teardown();
+    }
+    Ok(exit_code(status))
+}
+
+/// Tell the supervising parent that the handover is done (no-op unsupervised)
+pub fn notify_taken_over() {
+    if std::env::var_os(SUPERVISED_ENV).is_some() {
+        // SAFETY: plain kill(2) on our parent's PID
+        unsafe { libc::kill(libc::getppid(), libc::SIGUSR1) };
+    }
+}
+
+/// The child's exit code, or 128 + signal like a shell
+fn exit_code(status: ExitStatus) -> i32 {
+    status.code().or(status.signal().map(|signo| 128 + signo)).unwrap_or(1)
+}
+
+fn run_logged(command: &[String]) {
+    match network::run(command) {
+        Ok(_) => println!("[Network] {}", network::shell(command)),
+        Err(e) => eprintln!("[Network] {}: {}", network::shell(command), e),
+    }
+}
@@ -0,0 +1,67 @@
+//! Ring buffer of recent frames (pings excluded), so clients can watch for
+//! the mixer's answer to something they injected.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+
+use crate::protocol::Message;
+
+/// Frames kept; older ones are dropped
+pub const TRAFFIC_CAPACITY: usize = 512;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Direction {
+    /// GUI -> mixer (C->S)
+    ToMixer,
+    /// Mixer -> GUI (S->C)
+    FromMixer,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrafficEntry {
+    pub seq: u64,
+    pub direction: Direction,
+    /// Property name, if the frame parsed
+    pub property: Option<String>,
+    /// Hex-encoded frame payload
+    pub payload: String,
+}
+
+/// Reply to `Command::Traffic`
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct TrafficSnapshot {
+    /// Sequence number the next frame will get; pass it as `since` to poll
+    pub next_seq: u64,
+    pub entries: Vec<TrafficEntry>,
+}
+
+#[derive(Debug, Default)]
+pub struct TrafficLog {
+    entries: VecDeque<TrafficEntry>,
+    next_seq: u64,
+}
+
+impl TrafficLog {
+    pub fn record(&mut self, direction: Direction, payload: &[u8]) {
+        if self.entries.len() == TRAFFIC_CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(TrafficEntry {
+            seq: self.next_seq,
+            direction,
+            property: Message::parse(payload).map(|m| m.property.to_string()),
+            payload: hex::encode(payload),
+        });
+        self.next_seq += 1;
+    }
+
+    /// Entries with `seq >= since` (everything kept if `None`)
+    pub fn snapshot(&self, since: Option<u64>) -> TrafficSnapshot {
+        let since = since.unwrap_or(0);
+        TrafficSnapshot {
+            next_seq: self.next_seq,
+            entries: self.entries.iter().filter(|e| e.seq >= since).cloned().collect(),
+        }
+    }
+}